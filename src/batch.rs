@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use futures::stream::{self, BoxStream, StreamExt};
+use image::DynamicImage;
+
+use crate::{
+    LensClient, LensResult, Result, constants::DEFAULT_BATCH_PARALLELISM, image_processor,
+};
+
+/// An image to OCR.
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+    Image(DynamicImage),
+}
+
+impl ImageSource {
+    /// Decodes the source into an image.
+    pub fn into_image(self) -> Result<DynamicImage> {
+        match self {
+            ImageSource::Path(path) => image_processor::load_image_from_path(path),
+            ImageSource::Bytes(bytes) => image_processor::load_image_from_bytes(&bytes),
+            ImageSource::Image(img) => Ok(img),
+        }
+    }
+}
+
+impl From<PathBuf> for ImageSource {
+    fn from(path: PathBuf) -> Self {
+        ImageSource::Path(path)
+    }
+}
+
+impl From<&str> for ImageSource {
+    fn from(path: &str) -> Self {
+        ImageSource::Path(PathBuf::from(path))
+    }
+}
+
+impl From<Vec<u8>> for ImageSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        ImageSource::Bytes(bytes)
+    }
+}
+
+impl From<DynamicImage> for ImageSource {
+    fn from(img: DynamicImage) -> Self {
+        ImageSource::Image(img)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// Maximum number of images processed at once.
+    pub parallelism: usize,
+    /// Yield results in input order. When `false`, results are yielded as they complete.
+    pub preserve_order: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            parallelism: DEFAULT_BATCH_PARALLELISM,
+            preserve_order: true,
+        }
+    }
+}
+
+impl LensClient {
+    /// OCRs every source and streams `(index, result)` pairs, where `index` is the source's
+    /// position in `sources`. A failure only affects its own item.
+    ///
+    /// Reading, decoding, resizing and encoding run off the task polling the stream, on Tokio's
+    /// blocking thread pool, so `parallelism` covers image preparation as well as requests.
+    pub fn process_batch<'a, I>(
+        &'a self,
+        sources: I,
+        lang: Option<&'a str>,
+        options: BatchOptions,
+    ) -> BoxStream<'a, (usize, Result<LensResult>)>
+    where
+        I: IntoIterator<Item = ImageSource>,
+        I::IntoIter: Send + 'a,
+    {
+        let jobs =
+            sources
+                .into_iter()
+                .enumerate()
+                .map(move |(index, source)| async move {
+                    (index, self.process_source(source, lang).await)
+                });
+        let parallelism = options.parallelism.max(1);
+
+        if options.preserve_order {
+            stream::iter(jobs).buffered(parallelism).boxed()
+        } else {
+            stream::iter(jobs).buffer_unordered(parallelism).boxed()
+        }
+    }
+
+    pub async fn process_source(
+        &self,
+        source: ImageSource,
+        lang: Option<&str>,
+    ) -> Result<LensResult> {
+        match source {
+            ImageSource::Path(path) => {
+                let data = tokio::fs::read(path).await?;
+                self.process_encoded(data, lang).await
+            }
+            ImageSource::Bytes(bytes) => self.process_encoded(bytes, lang).await,
+            ImageSource::Image(img) => self.process_decoded(img, None, lang).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LensError;
+
+    #[tokio::test]
+    async fn undecodable_sources_fail_individually() {
+        let client = LensClient::new(None);
+        let sources = vec![
+            ImageSource::Bytes(b"not an image".to_vec()),
+            ImageSource::Path(PathBuf::from("/nonexistent/page.png")),
+        ];
+
+        let results: Vec<_> = client
+            .process_batch(sources, None, BatchOptions::default())
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 0);
+        assert!(
+            matches!(results[0].1, Err(LensError::ImageDecode(_))),
+            "{:?}",
+            results[0].1
+        );
+        assert!(
+            matches!(results[1].1, Err(LensError::Io(_))),
+            "{:?}",
+            results[1].1
+        );
+    }
+}
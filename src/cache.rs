@@ -0,0 +1,338 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+
+/// Identifies a cached response: a SHA-256 of the uploaded image bytes and the request language.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn new(image_bytes: &[u8], lang: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(image_bytes);
+        hasher.update([0u8]);
+        hasher.update(lang.as_bytes());
+
+        let hex = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        CacheKey(hex)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Storage for server responses, consulted by [`LensClient`](crate::LensClient) before uploading.
+///
+/// Values are the protobuf-encoded server responses rather than parsed results, so a cache hit is
+/// parsed exactly like a fresh response.
+pub trait ResultCache: Send + Sync + std::fmt::Debug {
+    fn get(&self, key: &CacheKey) -> Option<Bytes>;
+    fn put(&self, key: &CacheKey, response: Bytes);
+}
+
+// --- In-memory LRU ---
+
+/// Least-recently-used in-memory cache with an entry limit and optional TTL.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    entries: HashMap<CacheKey, MemoryEntry>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+    response: Bytes,
+    inserted: Instant,
+    last_used: u64,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl: None,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    /// Entries older than `ttl` are treated as missing.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+impl ResultCache for MemoryCache {
+    fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+
+        let expired = match state.entries.get(key) {
+            Some(entry) => self.ttl.is_some_and(|ttl| entry.inserted.elapsed() > ttl),
+            None => return None,
+        };
+        if expired {
+            state.entries.remove(key);
+            return None;
+        }
+
+        let entry = state.entries.get_mut(key)?;
+        entry.last_used = tick;
+        Some(entry.response.clone())
+    }
+
+    fn put(&self, key: &CacheKey, response: Bytes) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+
+        if !state.entries.contains_key(key) && state.entries.len() >= self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+
+        state.entries.insert(
+            key.clone(),
+            MemoryEntry {
+                response,
+                inserted: Instant::now(),
+                last_used: tick,
+            },
+        );
+    }
+}
+
+// --- File-backed ---
+
+/// Stores one file per entry in a directory, with an optional TTL and total size limit.
+///
+/// Both limits go by each file's modification time, which is set when the entry is written and
+/// not updated by reads: the TTL counts from the last write and the size limit evicts in
+/// first-in, first-out order rather than least-recently-used.
+///
+/// I/O failures are logged and treated as cache misses.
+#[derive(Debug)]
+pub struct FileCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+    max_bytes: Option<u64>,
+    lock: Mutex<()>,
+}
+
+impl FileCache {
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            ttl: None,
+            max_bytes: None,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Entries whose files are older than `ttl` are treated as missing and removed.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// When the directory grows past `max_bytes`, the earliest written entries are removed.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.pb", key.as_str()))
+    }
+
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        self.ttl
+            .is_some_and(|ttl| modified.elapsed().map(|age| age > ttl).unwrap_or(false))
+    }
+
+    fn enforce_size_limit(&self, max_bytes: u64) -> std::io::Result<()> {
+        let mut files = Vec::new();
+        let mut total = 0u64;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "pb") {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            total += meta.len();
+            files.push((meta.modified()?, meta.len(), path));
+        }
+
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total = total.saturating_sub(len);
+        }
+        Ok(())
+    }
+}
+
+impl ResultCache for FileCache {
+    fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        let path = self.entry_path(key);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if self.is_expired(modified) {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        match fs::read(&path) {
+            Ok(data) => Some(Bytes::from(data)),
+            Err(e) => {
+                log::warn!("Failed to read cache entry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &CacheKey, response: Bytes) {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let path = self.entry_path(key);
+        let tmp_path = path.with_extension("tmp");
+
+        if let Err(e) = fs::write(&tmp_path, &response).and_then(|_| fs::rename(&tmp_path, &path)) {
+            log::warn!("Failed to write cache entry {:?}: {}", path, e);
+            return;
+        }
+
+        if let Some(max_bytes) = self.max_bytes
+            && let Err(e) = self.enforce_size_limit(max_bytes)
+        {
+            log::warn!("Failed to trim cache directory {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, thread};
+
+    use super::*;
+
+    fn key(name: &str) -> CacheKey {
+        CacheKey::new(name.as_bytes(), "en")
+    }
+
+    fn value(name: &str) -> Bytes {
+        Bytes::from(name.repeat(10))
+    }
+
+    /// A fresh directory under the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("lens-cache-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn set_age(cache: &FileCache, key: &CacheKey, age: Duration) {
+        File::options()
+            .write(true)
+            .open(cache.entry_path(key))
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[test]
+    fn memory_cache_evicts_the_least_recently_read_entry() {
+        let cache = MemoryCache::new(2);
+        cache.put(&key("a"), value("a"));
+        cache.put(&key("b"), value("b"));
+        assert_eq!(cache.get(&key("a")), Some(value("a")));
+
+        cache.put(&key("c"), value("c"));
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("a")), Some(value("a")));
+        assert_eq!(cache.get(&key("c")), Some(value("c")));
+    }
+
+    #[test]
+    fn memory_cache_entries_expire_after_the_ttl() {
+        let cache = MemoryCache::new(2).with_ttl(Duration::from_millis(20));
+        cache.put(&key("a"), value("a"));
+        assert_eq!(cache.get(&key("a")), Some(value("a")));
+
+        thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.get(&key("a")), None);
+    }
+
+    #[test]
+    fn file_cache_entries_expire_after_the_ttl() {
+        let dir = TempDir::new("ttl");
+        let cache = FileCache::new(&dir.0)
+            .unwrap()
+            .with_ttl(Duration::from_secs(60));
+        cache.put(&key("a"), value("a"));
+        assert_eq!(cache.get(&key("a")), Some(value("a")));
+
+        set_age(&cache, &key("a"), Duration::from_secs(120));
+        assert_eq!(cache.get(&key("a")), None);
+        assert!(!cache.entry_path(&key("a")).exists());
+    }
+
+    #[test]
+    fn file_cache_evicts_the_earliest_written_entry() {
+        let dir = TempDir::new("fifo");
+        let cache = FileCache::new(&dir.0).unwrap().with_max_bytes(25);
+        cache.put(&key("a"), value("a"));
+        set_age(&cache, &key("a"), Duration::from_secs(20));
+        cache.put(&key("b"), value("b"));
+        set_age(&cache, &key("b"), Duration::from_secs(10));
+
+        // Reading does not refresh an entry.
+        assert_eq!(cache.get(&key("a")), Some(value("a")));
+        cache.put(&key("c"), value("c"));
+
+        assert_eq!(cache.get(&key("a")), None);
+        assert_eq!(cache.get(&key("b")), Some(value("b")));
+        assert_eq!(cache.get(&key("c")), Some(value("c")));
+    }
+}
@@ -4,3 +4,17 @@ pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64)
 pub const DEFAULT_IMAGE_MAX_DIMENSION: u32 = 1500;
 pub const DEFAULT_CLIENT_REGION: &str = "US";
 pub const DEFAULT_CLIENT_TIME_ZONE: &str = "America/New_York";
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 3;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 10_000;
+pub const DEFAULT_BATCH_PARALLELISM: usize = 4;
+pub const DEFAULT_TILE_OVERLAP: u32 = 200;
+pub const DEFAULT_TILE_PARALLELISM: usize = 4;
+pub const DEFAULT_DESKEW_MIN_ANGLE_DEG: f32 = 0.5;
+pub const DEFAULT_MAX_DOWNLOAD_BYTES: u64 = 20 * 1024 * 1024;
+pub const MAX_ERROR_BODY_BYTES: usize = 4 * 1024;
+pub const DEFAULT_PDF_DPI: f32 = 300.0;
+pub const DEFAULT_PDF_JPEG_QUALITY: u8 = 90;
+pub const DEFAULT_TRANSLATION_MIN_FONT_SIZE: f32 = 8.0;
+pub const DEFAULT_TRANSLATION_MAX_FONT_SIZE: f32 = 96.0;
@@ -0,0 +1,485 @@
+//! Multi-page input: animated GIF frames, multi-page TIFF and scanned PDF pages.
+
+use std::{io::Cursor, path::Path, sync::Arc};
+
+use futures::stream::{self, StreamExt};
+use image::{
+    AnimationDecoder, DynamicImage, GrayImage, ImageError, ImageFormat, RgbImage, RgbaImage,
+    codecs::gif::GifDecoder,
+    error::{DecodingError, ImageFormatHint},
+};
+use tiff::{
+    ColorType,
+    decoder::{Decoder as TiffDecoder, DecodingResult},
+};
+
+use crate::{
+    BatchOptions, LensClient, LensError, LensResult, Result, image_processor, run_blocking,
+};
+
+/// OCR output for every page of a document.
+#[derive(Debug)]
+pub struct DocumentResult {
+    /// One entry per page, in page order.
+    pub pages: Vec<PageResult>,
+}
+
+#[derive(Debug)]
+pub struct PageResult {
+    /// Zero-based page or frame index.
+    pub index: usize,
+    pub result: Result<LensResult>,
+}
+
+impl DocumentResult {
+    /// Text of all successfully processed pages, separated by blank lines.
+    pub fn full_text(&self) -> String {
+        self.pages
+            .iter()
+            .filter_map(|p| p.result.as_ref().ok())
+            .map(|r| r.full_text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Decodes every page of `data`. The outer error means the container itself could not be read;
+/// a page that fails to decode gets its own error and does not affect the others.
+///
+/// GIF yields one image per frame, TIFF one per directory and PDF the largest image on each page,
+/// which covers scanned documents but not vector text. PDF images must be JPEG (`DCTDecode`),
+/// unfiltered, or `FlateDecode`/`LZWDecode`/`ASCII85Decode` compressed 1-bit, gray or RGB
+/// pixels; pages using `CCITTFaxDecode`, `JBIG2Decode` or `JPXDecode` fail. Any other format
+/// yields a single page.
+pub fn load_pages(data: &[u8]) -> Result<Vec<Result<DynamicImage>>> {
+    let pages = Pages::open(data.to_vec())?;
+    Ok((0..pages.len()).map(|index| pages.decode(index)).collect())
+}
+
+pub fn load_pages_from_path<P: AsRef<Path>>(path: P) -> Result<Vec<Result<DynamicImage>>> {
+    load_pages(&std::fs::read(path)?)
+}
+
+/// A document whose container has been read but whose pages are decoded one at a time, so only
+/// the pages being worked on are held in memory.
+pub(crate) struct Pages {
+    kind: PagesKind,
+}
+
+enum PagesKind {
+    Pdf {
+        doc: Box<lopdf::Document>,
+        /// Page number and object of each page, in order.
+        pages: Vec<(u32, lopdf::ObjectId)>,
+    },
+    Tiff {
+        data: Vec<u8>,
+        count: usize,
+    },
+    /// GIF frames build on the previous frame, so they are decoded together up front.
+    Decoded(Vec<Result<DynamicImage>>),
+    Single(Vec<u8>),
+}
+
+impl Pages {
+    /// Reads the container of `data` and counts its pages without decoding them. See
+    /// [`load_pages`] for the supported formats.
+    pub(crate) fn open(data: Vec<u8>) -> Result<Self> {
+        let kind = if data.starts_with(b"%PDF-") {
+            let doc = lopdf::Document::load_mem(&data).map_err(|e| decode_error(pdf_hint(), e))?;
+            let pages = doc.get_pages().into_iter().collect();
+            PagesKind::Pdf {
+                doc: Box::new(doc),
+                pages,
+            }
+        } else {
+            match image::guess_format(&data) {
+                Ok(ImageFormat::Gif) => PagesKind::Decoded(load_gif_frames(&data)?),
+                Ok(ImageFormat::Tiff) => {
+                    let count = count_tiff_pages(&data)?;
+                    PagesKind::Tiff { data, count }
+                }
+                _ => PagesKind::Single(data),
+            }
+        };
+        Ok(Self { kind })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match &self.kind {
+            PagesKind::Pdf { pages, .. } => pages.len(),
+            PagesKind::Tiff { count, .. } => *count,
+            PagesKind::Decoded(frames) => frames.len(),
+            PagesKind::Single(_) => 1,
+        }
+    }
+
+    /// Decodes page `index`, which must be less than [`len`](Self::len).
+    pub(crate) fn decode(&self, index: usize) -> Result<DynamicImage> {
+        match &self.kind {
+            PagesKind::Pdf { doc, pages } => {
+                let (page_number, page_id) = pages[index];
+                load_pdf_page(doc, page_id).map_err(|e| match e {
+                    LensError::ImageDecode(inner) => {
+                        decode_error(pdf_hint(), format!("page {}: {}", page_number, inner))
+                    }
+                    other => other,
+                })
+            }
+            PagesKind::Tiff { data, .. } => load_tiff_page(data, index),
+            PagesKind::Decoded(frames) => match &frames[index] {
+                Ok(frame) => Ok(frame.clone()),
+                Err(e) => Err(decode_error(ImageFormat::Gif.into(), e.to_string())),
+            },
+            PagesKind::Single(data) => image_processor::load_image_from_bytes(data),
+        }
+    }
+}
+
+/// Frames decode in sequence, so decoding stops at the first bad frame, which is recorded as
+/// the last page.
+fn load_gif_frames(data: &[u8]) -> Result<Vec<Result<DynamicImage>>> {
+    let decoder = GifDecoder::new(Cursor::new(data)).map_err(LensError::ImageDecode)?;
+
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames() {
+        match frame {
+            Ok(frame) => frames.push(Ok(DynamicImage::ImageRgba8(frame.into_buffer()))),
+            Err(e) => {
+                frames.push(Err(LensError::ImageDecode(e)));
+                break;
+            }
+        }
+    }
+    Ok(frames)
+}
+
+fn tiff_error(e: tiff::TiffError) -> LensError {
+    decode_error(ImageFormat::Tiff.into(), e)
+}
+
+/// Walks the directory chain, which holds page metadata only. A broken link counts as one more
+/// page so the failure is reported for it when it is decoded.
+fn count_tiff_pages(data: &[u8]) -> Result<usize> {
+    let mut decoder = TiffDecoder::new(Cursor::new(data)).map_err(tiff_error)?;
+    let mut count = 1;
+    while decoder.more_images() {
+        count += 1;
+        if decoder.next_image().is_err() {
+            break;
+        }
+    }
+    Ok(count)
+}
+
+fn load_tiff_page(data: &[u8], index: usize) -> Result<DynamicImage> {
+    let mut decoder = TiffDecoder::new(Cursor::new(data)).map_err(tiff_error)?;
+    decoder.seek_to_image(index).map_err(tiff_error)?;
+
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let color = decoder.colortype().map_err(tiff_error)?;
+    let buffer = decoder.read_image().map_err(tiff_error)?;
+    tiff_page(width, height, color, buffer)
+}
+
+fn tiff_page(
+    width: u32,
+    height: u32,
+    color: ColorType,
+    buffer: DecodingResult,
+) -> Result<DynamicImage> {
+    let img = match (color, buffer) {
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            GrayImage::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(1), DecodingResult::U8(buf)) => Some(DynamicImage::ImageLuma8(
+            unpack_bilevel(width, height, &buf),
+        )),
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            RgbImage::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            RgbaImage::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            image::ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (color, _) => {
+            return Err(decode_error(
+                ImageFormat::Tiff.into(),
+                format!("unsupported TIFF page color type {:?}", color),
+            ));
+        }
+    };
+
+    img.ok_or_else(|| decode_error(ImageFormat::Tiff.into(), "TIFF page buffer too small"))
+}
+
+/// Expands 1-bit rows, each padded to a whole byte, to 8-bit grayscale.
+fn unpack_bilevel(width: u32, height: u32, packed: &[u8]) -> GrayImage {
+    let stride = width.div_ceil(8) as usize;
+    GrayImage::from_fn(width, height, |x, y| {
+        let byte = packed
+            .get(y as usize * stride + x as usize / 8)
+            .copied()
+            .unwrap_or(0);
+        let bit = (byte >> (7 - x % 8)) & 1;
+        image::Luma([if bit == 1 { 255 } else { 0 }])
+    })
+}
+
+fn pdf_hint() -> ImageFormatHint {
+    ImageFormatHint::Name("PDF".to_string())
+}
+
+fn load_pdf_page(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> Result<DynamicImage> {
+    let images = doc
+        .get_page_images(page_id)
+        .map_err(|e| decode_error(pdf_hint(), e))?;
+    let largest = images
+        .iter()
+        .max_by_key(|img| img.width * img.height)
+        .ok_or_else(|| decode_error(pdf_hint(), "page contains no image"))?;
+
+    pdf_image(doc, largest)
+}
+
+/// Image filters with no decoder here; see [`load_pages`].
+const UNSUPPORTED_PDF_FILTERS: [&str; 3] = ["CCITTFaxDecode", "JBIG2Decode", "JPXDecode"];
+
+fn pdf_image(doc: &lopdf::Document, img: &lopdf::xobject::PdfImage) -> Result<DynamicImage> {
+    let filters = img.filters.clone().unwrap_or_default();
+
+    if let Some(filter) = filters
+        .iter()
+        .find(|f| UNSUPPORTED_PDF_FILTERS.contains(&f.as_str()))
+    {
+        return Err(decode_error(
+            pdf_hint(),
+            format!("{} images are not supported", filter),
+        ));
+    }
+    if filters.iter().any(|f| f == "DCTDecode") {
+        return image_processor::load_image_from_bytes(img.content);
+    }
+
+    let stream = doc
+        .get_object(img.id)
+        .and_then(|obj| obj.as_stream())
+        .map_err(|e| decode_error(pdf_hint(), e))?;
+    let raw = if filters.is_empty() {
+        stream.content.clone()
+    } else {
+        stream
+            .decompressed_content()
+            .map_err(|e| decode_error(pdf_hint(), e))?
+    };
+
+    let (width, height) = (img.width.max(0) as u32, img.height.max(0) as u32);
+    let pixels = width as usize * height as usize;
+    let decoded = match img.bits_per_component {
+        Some(1) => Some(DynamicImage::ImageLuma8(unpack_bilevel(
+            width, height, &raw,
+        ))),
+        _ if pixels > 0 && raw.len() == pixels => {
+            GrayImage::from_raw(width, height, raw).map(DynamicImage::ImageLuma8)
+        }
+        _ if pixels > 0 && raw.len() == pixels * 3 => {
+            RgbImage::from_raw(width, height, raw).map(DynamicImage::ImageRgb8)
+        }
+        _ => None,
+    };
+
+    decoded.ok_or_else(|| {
+        decode_error(
+            pdf_hint(),
+            format!("unsupported image encoding (filters {:?})", filters),
+        )
+    })
+}
+
+fn decode_error(
+    format: ImageFormatHint,
+    err: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> LensError {
+    LensError::ImageDecode(ImageError::Decoding(DecodingError::new(format, err)))
+}
+
+impl LensClient {
+    /// OCRs every page or frame of a document. A failure on one page, in decoding or OCR, is
+    /// recorded in its [`PageResult`] and does not stop the others.
+    ///
+    /// Pages are decoded on the blocking thread pool as they are needed, so at most
+    /// `options.parallelism` decoded pages are held at once (GIF frames excepted, see
+    /// [`load_pages`]).
+    pub async fn process_document(
+        &self,
+        data: &[u8],
+        lang: Option<&str>,
+        options: BatchOptions,
+    ) -> Result<DocumentResult> {
+        self.process_document_data(data.to_vec(), lang, options)
+            .await
+    }
+
+    pub async fn process_document_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+        lang: Option<&str>,
+        options: BatchOptions,
+    ) -> Result<DocumentResult> {
+        let data = tokio::fs::read(path).await?;
+        self.process_document_data(data, lang, options).await
+    }
+
+    async fn process_document_data(
+        &self,
+        data: Vec<u8>,
+        lang: Option<&str>,
+        options: BatchOptions,
+    ) -> Result<DocumentResult> {
+        let pages = Arc::new(run_blocking(move || Pages::open(data)).await?);
+
+        let jobs = (0..pages.len()).map(|index| {
+            let pages = Arc::clone(&pages);
+            async move {
+                let result = match run_blocking(move || pages.decode(index)).await {
+                    Ok(img) => self.process_decoded(img, None, lang).await,
+                    Err(e) => Err(e),
+                };
+                PageResult { index, result }
+            }
+        });
+        // Pages are sorted below, so they may finish in any order.
+        let mut results: Vec<PageResult> = stream::iter(jobs)
+            .buffer_unordered(options.parallelism.max(1))
+            .collect()
+            .await;
+        results.sort_by_key(|p| p.index);
+
+        Ok(DocumentResult { pages: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Document, Object, Stream, dictionary};
+
+    use super::*;
+
+    /// A PDF whose pages hold, in order: a 2x2 gray image, nothing, and a JBIG2 image.
+    fn mixed_pdf() -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+
+        let image = |filter: Option<&str>| {
+            let mut dict = dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 2,
+                "Height" => 2,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            };
+            if let Some(filter) = filter {
+                dict.set("Filter", Object::Name(filter.as_bytes().to_vec()));
+            }
+            Stream::new(dict, vec![0, 64, 128, 255])
+        };
+        let gray = doc.add_object(image(None));
+        let jbig2 = doc.add_object(image(Some("JBIG2Decode")));
+
+        let mut kids = Vec::new();
+        for xobject in [Some(gray), None, Some(jbig2)] {
+            let resources = match xobject {
+                Some(id) => dictionary! { "XObject" => dictionary! { "Im0" => id } },
+                None => dictionary! {},
+            };
+            let content = doc.add_object(Stream::new(
+                dictionary! {},
+                b"q 2 0 0 2 0 0 cm /Im0 Do Q".to_vec(),
+            ));
+            let page = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 2.into(), 2.into()],
+                "Resources" => resources,
+                "Contents" => content,
+            });
+            kids.push(Object::Reference(page));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+            }),
+        );
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+
+        let mut out = Vec::new();
+        doc.save_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn pdf_pages_fail_independently() {
+        let pages = load_pages(&mixed_pdf()).unwrap();
+        assert_eq!(pages.len(), 3);
+
+        let first = pages[0].as_ref().unwrap().to_luma8();
+        assert_eq!(first.dimensions(), (2, 2));
+        assert_eq!(first.as_raw(), &[0, 64, 128, 255]);
+
+        let missing = pages[1].as_ref().unwrap_err().to_string();
+        assert!(missing.contains("page 2"), "{}", missing);
+        assert!(missing.contains("no image"), "{}", missing);
+
+        let jbig2 = pages[2].as_ref().unwrap_err().to_string();
+        assert!(jbig2.contains("JBIG2Decode"), "{}", jbig2);
+    }
+
+    #[test]
+    fn unreadable_container_is_an_error() {
+        assert!(load_pages(b"%PDF-1.5 truncated").is_err());
+    }
+
+    #[test]
+    fn single_images_are_one_page() {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(3, 2)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+
+        let pages = load_pages(png.get_ref()).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].is_ok());
+    }
+
+    #[test]
+    fn tiff_pages_are_decoded_on_demand() {
+        let mut tiff = Cursor::new(Vec::new());
+        let mut encoder = tiff::encoder::TiffEncoder::new(&mut tiff).unwrap();
+        for (width, value) in [(2, 10u8), (3, 20), (4, 30)] {
+            encoder
+                .write_image::<tiff::encoder::colortype::Gray8>(
+                    width,
+                    1,
+                    &vec![value; width as usize],
+                )
+                .unwrap();
+        }
+
+        let pages = Pages::open(tiff.into_inner()).unwrap();
+        assert_eq!(pages.len(), 3);
+        let last = pages.decode(2).unwrap().to_luma8();
+        assert_eq!(last.as_raw(), &[30; 4]);
+        let first = pages.decode(0).unwrap().to_luma8();
+        assert_eq!(first.as_raw(), &[10; 2]);
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Errors returned by [`LensClient`](crate::LensClient) and
+/// [`image_processor`](crate::image_processor).
+#[derive(Debug, Error)]
+pub enum LensError {
+    #[error("Failed to read image: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to decode image: {0}")]
+    ImageDecode(#[source] image::ImageError),
+
+    #[error("Failed to encode image: {0}")]
+    ImageEncode(#[source] image::ImageError),
+
+    #[error("Invalid font: {0}")]
+    InvalidFont(String),
+
+    #[error("Invalid raw image buffer: {0}")]
+    InvalidBuffer(String),
+
+    #[error("Invalid URL {0}")]
+    InvalidUrl(String),
+
+    /// A downloaded resource is not an image.
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContentType(String),
+
+    #[error("Download exceeds the {limit} byte limit")]
+    DownloadTooLarge { limit: u64 },
+
+    #[error("Invalid client configuration: {0}")]
+    Config(String),
+
+    /// A requested region does not overlap the image.
+    #[error("Region lies outside the {width}x{height} image")]
+    InvalidRegion { width: u32, height: u32 },
+
+    #[error("Request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+
+    #[error("Transport error: {0}")]
+    Transport(#[source] reqwest::Error),
+
+    /// The server answered with HTTP 429. `retry_after` is taken from the `Retry-After` header.
+    #[error("Rate limited by server (retry after {retry_after:?}): {body}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        body: String,
+    },
+
+    /// The server answered with a non-2xx status other than 429.
+    #[error("API Error {status}: {body}")]
+    Http {
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Failed to encode protobuf request: {0}")]
+    ProtobufEncode(#[from] prost::EncodeError),
+
+    #[error("Failed to decode protobuf response: {0}")]
+    ProtobufDecode(#[from] prost::DecodeError),
+
+    /// The response decoded successfully but held no paragraphs and no translation, as for an
+    /// image without text.
+    #[error("No text found in image")]
+    EmptyResult,
+}
+
+impl From<reqwest::Error> for LensError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            LensError::Timeout(e)
+        } else {
+            LensError::Transport(e)
+        }
+    }
+}
+
+impl LensError {
+    /// The server-provided `Retry-After` delay, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LensError::RateLimited { retry_after, .. } | LensError::Http { retry_after, .. } => {
+                *retry_after
+            }
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, LensError>;
@@ -0,0 +1,233 @@
+//! ALTO v4 output.
+
+use std::fmt::Write;
+
+use super::{OCR_SOFTWARE, OCR_VERSION, counter_clockwise_degrees, escape_xml, pixel_bbox};
+use crate::{GeometryData, LensResult, image_processor::ImageDimensions};
+
+const ALTO_NAMESPACE: &str = "http://www.loc.gov/standards/alto/ns-v4#";
+const ALTO_SCHEMA: &str = "http://www.loc.gov/alto/v4/alto-4-2.xsd";
+
+/// Renders one page as an ALTO document. `image_name` is recorded as the source file name.
+pub fn to_alto(result: &LensResult, image_name: Option<&str>) -> String {
+    to_alto_pages(&[(result, image_name)])
+}
+
+/// Renders several pages into one ALTO document, one `Page` per entry.
+pub fn to_alto_pages(pages: &[(&LensResult, Option<&str>)]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<alto xmlns=\"{0}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"{0} {1}\">",
+        ALTO_NAMESPACE, ALTO_SCHEMA
+    );
+
+    out.push_str("<Description>\n<MeasurementUnit>pixel</MeasurementUnit>\n");
+    if let Some(name) = pages.iter().find_map(|(_, name)| *name) {
+        let _ = writeln!(
+            out,
+            "<sourceImageInformation><fileName>{}</fileName></sourceImageInformation>",
+            escape_xml(name)
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<OCRProcessing ID=\"ocr_1\"><ocrProcessingStep><processingSoftware>\
+         <softwareName>{}</softwareName><softwareVersion>{}</softwareVersion>\
+         </processingSoftware></ocrProcessingStep></OCRProcessing>",
+        OCR_SOFTWARE, OCR_VERSION
+    );
+    out.push_str("</Description>\n<Layout>\n");
+
+    for (index, (result, _)) in pages.iter().enumerate() {
+        write_page(&mut out, result, index + 1);
+    }
+
+    out.push_str("</Layout>\n</alto>\n");
+    out
+}
+
+fn write_page(out: &mut String, result: &LensResult, page: usize) {
+    let dims = &result.image;
+    let _ = writeln!(
+        out,
+        "<Page ID=\"page_{0}\" PHYSICAL_IMG_NR=\"{0}\" WIDTH=\"{1}\" HEIGHT=\"{2}\">",
+        page, dims.original_width, dims.original_height
+    );
+    let _ = writeln!(
+        out,
+        "<PrintSpace ID=\"space_{}\" HPOS=\"0\" VPOS=\"0\" WIDTH=\"{}\" HEIGHT=\"{}\">",
+        page, dims.original_width, dims.original_height
+    );
+
+    let mut line_id = 0;
+    let mut word_id = 0;
+    for (p_index, paragraph) in result.paragraphs.iter().enumerate() {
+        let mut attrs = position(paragraph.geometry.as_ref(), dims);
+        if let Some(g) = &paragraph.geometry {
+            let rotation = counter_clockwise_degrees(g);
+            if rotation.abs() >= 0.01 {
+                let _ = write!(attrs, " ROTATION=\"{:.2}\"", rotation);
+            }
+        }
+        if let Some(lang) = paragraph.language.as_deref().or(result.language.as_deref()) {
+            let _ = write!(attrs, " LANG=\"{}\"", escape_xml(lang));
+        }
+        let _ = writeln!(
+            out,
+            "<TextBlock ID=\"block_{}_{}\"{}>",
+            page,
+            p_index + 1,
+            attrs
+        );
+
+        for line in &paragraph.lines {
+            line_id += 1;
+            let _ = writeln!(
+                out,
+                "<TextLine ID=\"line_{}_{}\"{}>",
+                page,
+                line_id,
+                position(line.geometry.as_ref(), dims)
+            );
+
+            if line.words.is_empty() {
+                // A TextLine must hold at least one String.
+                word_id += 1;
+                write_string(out, page, word_id, &line.text, line.geometry.as_ref(), dims);
+            }
+            for (w_index, word) in line.words.iter().enumerate() {
+                word_id += 1;
+                write_string(out, page, word_id, &word.text, word.geometry.as_ref(), dims);
+                if !word.separator.is_empty() && w_index + 1 < line.words.len() {
+                    out.push_str("<SP/>\n");
+                }
+            }
+            out.push_str("</TextLine>\n");
+        }
+        out.push_str("</TextBlock>\n");
+    }
+    out.push_str("</PrintSpace>\n</Page>\n");
+}
+
+fn write_string(
+    out: &mut String,
+    page: usize,
+    id: usize,
+    text: &str,
+    geometry: Option<&GeometryData>,
+    dims: &ImageDimensions,
+) {
+    let _ = writeln!(
+        out,
+        "<String ID=\"string_{}_{}\" CONTENT=\"{}\"{}/>",
+        page,
+        id,
+        escape_xml(text),
+        position(geometry, dims)
+    );
+}
+
+/// `HPOS`, `VPOS`, `WIDTH` and `HEIGHT` attributes from the element's pixel bounding box.
+fn position(geometry: Option<&GeometryData>, dims: &ImageDimensions) -> String {
+    let (x0, y0, x1, y1) = geometry.map_or((0, 0, 0, 0), |g| pixel_bbox(g, dims));
+    format!(
+        " HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        x0,
+        y0,
+        x1 - x0,
+        y1 - y0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::{Document, Node};
+
+    use super::to_alto;
+    use crate::export::tests::fixture;
+
+    fn elements<'a, 'i>(node: Node<'a, 'i>) -> Vec<Node<'a, 'i>> {
+        node.children().filter(Node::is_element).collect()
+    }
+
+    fn names(nodes: &[Node]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| n.tag_name().name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn nests_blocks_lines_and_strings() {
+        let xml = to_alto(&fixture(), Some("page.png"));
+        let doc = Document::parse(&xml).unwrap();
+
+        let layout = elements(doc.root_element())
+            .into_iter()
+            .find(|n| n.has_tag_name("Layout"))
+            .unwrap();
+        let pages = elements(layout);
+        assert_eq!(names(&pages), ["Page"]);
+        let spaces = elements(pages[0]);
+        assert_eq!(names(&spaces), ["PrintSpace"]);
+        let blocks = elements(spaces[0]);
+        assert_eq!(names(&blocks), ["TextBlock", "TextBlock"]);
+
+        for block in &blocks {
+            for line in elements(*block) {
+                assert!(line.has_tag_name("TextLine"));
+                let children = names(&elements(line));
+                assert_eq!(children.first().map(String::as_str), Some("String"));
+                assert_eq!(children.last().map(String::as_str), Some("String"));
+                assert!(children.iter().all(|n| n == "String" || n == "SP"));
+            }
+        }
+
+        let strings: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("String"))
+            .map(|n| n.attribute("CONTENT").unwrap())
+            .collect();
+        assert_eq!(strings, ["<a>", "b&c", "d", "e"]);
+        assert!(xml.contains("CONTENT=\"&lt;a&gt;\""));
+        assert!(xml.contains("CONTENT=\"b&amp;c\""));
+    }
+
+    #[test]
+    fn positions_are_integers() {
+        let xml = to_alto(&fixture(), None);
+        let doc = Document::parse(&xml).unwrap();
+
+        for node in doc.descendants().filter(|n| n.has_attribute("HPOS")) {
+            for attr in ["HPOS", "VPOS", "WIDTH", "HEIGHT"] {
+                let value = node.attribute(attr).unwrap();
+                assert!(
+                    value.parse::<u32>().is_ok(),
+                    "{} {}={}",
+                    node.tag_name().name(),
+                    attr,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_is_counter_clockwise_and_language_is_kept() {
+        let xml = to_alto(&fixture(), None);
+        let doc = Document::parse(&xml).unwrap();
+        let blocks: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TextBlock"))
+            .collect();
+
+        // The fixture's first paragraph is rotated 10 degrees clockwise.
+        assert_eq!(blocks[0].attribute("ROTATION"), Some("-10.00"));
+        assert_eq!(blocks[0].attribute("LANG"), Some("ja"));
+        assert_eq!(blocks[1].attribute("ROTATION"), None);
+        assert_eq!(blocks[1].attribute("LANG"), Some("en"));
+    }
+}
@@ -0,0 +1,226 @@
+//! hOCR 1.2 output.
+
+use std::fmt::Write;
+
+use super::{OCR_SOFTWARE, OCR_VERSION, counter_clockwise_degrees, escape_xml, pixel_bbox};
+use crate::{GeometryData, LensResult, image_processor::ImageDimensions};
+
+/// Renders one page as an hOCR document. `image_name` is recorded in the page's `image` property.
+pub fn to_hocr(result: &LensResult, image_name: Option<&str>) -> String {
+    to_hocr_pages(&[(result, image_name)])
+}
+
+/// Renders several pages into one hOCR document, one `ocr_page` per entry.
+pub fn to_hocr_pages(pages: &[(&LensResult, Option<&str>)]) -> String {
+    let lang = pages
+        .iter()
+        .find_map(|(r, _)| r.language.as_deref())
+        .unwrap_or("und");
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \
+         \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+    );
+    let _ = writeln!(
+        out,
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"{0}\" lang=\"{0}\">",
+        escape_xml(lang)
+    );
+    out.push_str("<head>\n<title></title>\n");
+    out.push_str("<meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>\n");
+    let _ = writeln!(
+        out,
+        "<meta name=\"ocr-system\" content=\"{} {}\"/>",
+        OCR_SOFTWARE, OCR_VERSION
+    );
+    out.push_str(
+        "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_par ocr_line ocrx_word\"/>\n",
+    );
+    out.push_str("</head>\n<body>\n");
+
+    for (index, (result, image_name)) in pages.iter().enumerate() {
+        write_page(&mut out, result, index + 1, *image_name);
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn write_page(out: &mut String, result: &LensResult, page: usize, image_name: Option<&str>) {
+    let dims = &result.image;
+    let mut title = String::new();
+    if let Some(name) = image_name {
+        let _ = write!(title, "image \"{}\"; ", name.replace('"', "\\\""));
+    }
+    let _ = write!(
+        title,
+        "bbox 0 0 {} {}; ppageno {}",
+        dims.original_width,
+        dims.original_height,
+        page - 1
+    );
+    let _ = writeln!(
+        out,
+        "<div class=\"ocr_page\" id=\"page_{}\" title=\"{}\">",
+        page,
+        escape_xml(&title)
+    );
+
+    let mut line_id = 0;
+    let mut word_id = 0;
+    for (p_index, paragraph) in result.paragraphs.iter().enumerate() {
+        let lang = paragraph.language.as_deref().or(result.language.as_deref());
+        let lang_attr = lang
+            .map(|l| format!(" lang=\"{}\"", escape_xml(l)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "<p class=\"ocr_par\" id=\"par_{}_{}\"{} title=\"{}\">",
+            page,
+            p_index + 1,
+            lang_attr,
+            properties(paragraph.geometry.as_ref(), dims)
+        );
+
+        for line in &paragraph.lines {
+            line_id += 1;
+            let _ = writeln!(
+                out,
+                "<span class=\"ocr_line\" id=\"line_{}_{}\" title=\"{}\">",
+                page,
+                line_id,
+                properties(line.geometry.as_ref(), dims)
+            );
+
+            for word in &line.words {
+                word_id += 1;
+                let _ = write!(
+                    out,
+                    "<span class=\"ocrx_word\" id=\"word_{}_{}\" title=\"{}\">{}</span>",
+                    page,
+                    word_id,
+                    properties(word.geometry.as_ref(), dims),
+                    escape_xml(&word.text)
+                );
+                if !word.separator.is_empty() {
+                    out.push_str(&escape_xml(&word.separator));
+                }
+            }
+            out.push_str("\n</span>\n");
+        }
+        out.push_str("</p>\n");
+    }
+    out.push_str("</div>\n");
+}
+
+/// The `title` properties for an element: `bbox`, plus `textangle` for rotated boxes.
+fn properties(geometry: Option<&GeometryData>, dims: &ImageDimensions) -> String {
+    let Some(g) = geometry else {
+        return String::new();
+    };
+
+    let (x0, y0, x1, y1) = pixel_bbox(g, dims);
+    let mut props = format!("bbox {} {} {} {}", x0, y0, x1, y1);
+    let textangle = counter_clockwise_degrees(g);
+    if textangle.abs() >= 0.01 {
+        let _ = write!(props, "; textangle {:.2}", textangle);
+    }
+    props
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::{Document, Node, ParsingOptions};
+
+    use super::to_hocr;
+    use crate::export::tests::fixture;
+
+    fn parse(xml: &str) -> Document<'_> {
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        Document::parse_with_options(xml, options).unwrap()
+    }
+
+    fn elements<'a, 'i>(node: Node<'a, 'i>) -> Vec<Node<'a, 'i>> {
+        node.children().filter(Node::is_element).collect()
+    }
+
+    fn classes(nodes: &[Node]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| n.attribute("class").unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn nests_pages_paragraphs_lines_and_words() {
+        let xml = to_hocr(&fixture(), Some("page.png"));
+        let doc = parse(&xml);
+
+        let body = elements(doc.root_element())
+            .into_iter()
+            .find(|n| n.has_tag_name("body"))
+            .unwrap();
+        let pages = elements(body);
+        assert_eq!(classes(&pages), ["ocr_page"]);
+        let paragraphs = elements(pages[0]);
+        assert_eq!(classes(&paragraphs), ["ocr_par", "ocr_par"]);
+
+        let mut words = Vec::new();
+        for paragraph in &paragraphs {
+            for line in elements(*paragraph) {
+                assert_eq!(line.attribute("class"), Some("ocr_line"));
+                for word in elements(line) {
+                    assert_eq!(word.attribute("class"), Some("ocrx_word"));
+                    words.push(word.text().unwrap().to_string());
+                }
+            }
+        }
+        assert_eq!(words, ["<a>", "b&c", "d", "e"]);
+        assert!(xml.contains(">&lt;a&gt;</span>"));
+        assert!(xml.contains(">b&amp;c</span>"));
+    }
+
+    #[test]
+    fn bboxes_are_integers() {
+        let xml = to_hocr(&fixture(), None);
+        let doc = parse(&xml);
+
+        let titles: Vec<_> = doc
+            .descendants()
+            .filter_map(|n| n.attribute("title"))
+            .filter(|t| !t.is_empty())
+            .collect();
+        // The page, the rotated paragraph, its line and its two placed words.
+        assert_eq!(titles.len(), 5);
+        for title in titles {
+            let bbox = title
+                .split("; ")
+                .find_map(|p| p.strip_prefix("bbox "))
+                .unwrap();
+            let values: Vec<_> = bbox.split(' ').map(str::parse::<u32>).collect();
+            assert_eq!(values.len(), 4, "{}", title);
+            assert!(values.iter().all(Result::is_ok), "{}", title);
+        }
+    }
+
+    #[test]
+    fn textangle_is_counter_clockwise() {
+        let xml = to_hocr(&fixture(), None);
+        let doc = parse(&xml);
+        let paragraphs: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.attribute("class") == Some("ocr_par"))
+            .collect();
+
+        // The fixture's first paragraph is rotated 10 degrees clockwise.
+        let title = paragraphs[0].attribute("title").unwrap();
+        assert!(title.ends_with("; textangle -10.00"), "{}", title);
+        assert_eq!(paragraphs[0].attribute("lang"), Some("ja"));
+        assert_eq!(paragraphs[1].attribute("title"), Some(""));
+    }
+}
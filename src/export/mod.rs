@@ -0,0 +1,78 @@
+//! Renderers that turn a [`LensResult`](crate::LensResult) into standard OCR interchange formats
+//! and searchable PDFs.
+
+pub mod alto;
+pub mod hocr;
+pub mod page_xml;
+pub mod pdf;
+
+use crate::{GeometryData, image_processor::ImageDimensions};
+
+pub(crate) const OCR_SOFTWARE: &str = env!("CARGO_PKG_NAME");
+pub(crate) const OCR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Integer pixel bounding box `(x0, y0, x1, y1)` of `g` in the original image, clamped to it.
+pub(crate) fn pixel_bbox(g: &GeometryData, dims: &ImageDimensions) -> (u32, u32, u32, u32) {
+    let (w, h) = (dims.original_width, dims.original_height);
+    let rect = g.bounding_rect(w, h);
+    let clamp = |v: f32, max: u32| v.round().clamp(0.0, max as f32) as u32;
+
+    (
+        clamp(rect.x, w),
+        clamp(rect.y, h),
+        clamp(rect.right(), w),
+        clamp(rect.bottom(), h),
+    )
+}
+
+/// The rotation of `g` in degrees counter-clockwise, the convention of hOCR, ALTO, PAGE and PDF.
+/// Ours is clockwise because the image y axis points down.
+pub(crate) fn counter_clockwise_degrees(g: &GeometryData) -> f32 {
+    -g.angle_deg
+}
+
+/// Escapes text for use in XML content and attribute values.
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{
+        LensResult,
+        test_support::{geometry, line, paragraph, result, word},
+    };
+
+    /// Two paragraphs on an 800x600 page: a rotated Japanese one with markup characters and a
+    /// word without geometry, and one with no geometry at all.
+    pub fn fixture() -> LensResult {
+        let rotated = paragraph(
+            vec![line(
+                vec![
+                    word("<a>", " ", Some(geometry(0.3, 0.2, 0.1, 0.05, 10.0))),
+                    word("b&c", " ", Some(geometry(0.45, 0.22, 0.1, 0.05, 10.0))),
+                    word("d", "", None),
+                ],
+                Some(geometry(0.4, 0.21, 0.4, 0.06, 10.0)),
+            )],
+            Some(geometry(0.4, 0.21, 0.45, 0.1, 10.0)),
+            Some("ja"),
+        );
+        let unplaced = paragraph(vec![line(vec![word("e", "", None)], None)], None, None);
+
+        let mut result = result(vec![rotated, unplaced], 800, 600);
+        result.language = Some("en".to_string());
+        result
+    }
+}
@@ -0,0 +1,307 @@
+//! PRImA PAGE XML (2019-07-15 schema) output.
+
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{OCR_SOFTWARE, OCR_VERSION, counter_clockwise_degrees, escape_xml};
+use crate::{GeometryData, LensResult, image_processor::ImageDimensions};
+
+const PAGE_NAMESPACE: &str = "http://schema.primaresearch.org/PAGE/gts/pagecontent/2019-07-15";
+
+/// Renders a result as a PAGE XML document. `image_name` becomes the page's `imageFilename`.
+pub fn to_page_xml(result: &LensResult, image_name: Option<&str>) -> String {
+    let dims = &result.image;
+    let now = timestamp();
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<PcGts xmlns=\"{0}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"{0} {0}/pagecontent.xsd\">",
+        PAGE_NAMESPACE
+    );
+    let _ = writeln!(
+        out,
+        "<Metadata>\n<Creator>{} {}</Creator>\n<Created>{}</Created>\n\
+         <LastChange>{}</LastChange>\n</Metadata>",
+        OCR_SOFTWARE, OCR_VERSION, now, now
+    );
+
+    let mut page_attrs = format!(
+        " imageFilename=\"{}\" imageWidth=\"{}\" imageHeight=\"{}\"",
+        escape_xml(image_name.unwrap_or("")),
+        dims.original_width,
+        dims.original_height
+    );
+    if let Some(lang) = result.language.as_deref().and_then(language_name) {
+        let _ = write!(page_attrs, " primaryLanguage=\"{}\"", lang);
+    }
+    let _ = writeln!(out, "<Page{}>", page_attrs);
+
+    if !result.paragraphs.is_empty() {
+        out.push_str("<ReadingOrder>\n<OrderedGroup id=\"ro_1\">\n");
+        for index in 0..result.paragraphs.len() {
+            let _ = writeln!(
+                out,
+                "<RegionRefIndexed index=\"{0}\" regionRef=\"region_{1}\"/>",
+                index,
+                index + 1
+            );
+        }
+        out.push_str("</OrderedGroup>\n</ReadingOrder>\n");
+    }
+
+    let mut line_id = 0;
+    let mut word_id = 0;
+    for (p_index, paragraph) in result.paragraphs.iter().enumerate() {
+        let language = paragraph
+            .language
+            .as_deref()
+            .or(result.language.as_deref())
+            .and_then(language_name);
+
+        let mut attrs = format!(" id=\"region_{}\" type=\"paragraph\"", p_index + 1);
+        if let Some(g) = &paragraph.geometry
+            && g.angle_deg.abs() >= 0.01
+        {
+            // The clockwise turn that levels the region is the region's counter-clockwise angle.
+            let _ = write!(
+                attrs,
+                " orientation=\"{:.2}\"",
+                counter_clockwise_degrees(g)
+            );
+        }
+        if let Some(lang) = language {
+            let _ = write!(attrs, " primaryLanguage=\"{}\"", lang);
+        }
+        let _ = writeln!(out, "<TextRegion{}>", attrs);
+        write_coords(&mut out, paragraph.geometry.as_ref(), dims);
+
+        for line in &paragraph.lines {
+            line_id += 1;
+            let mut attrs = format!(" id=\"line_{}\"", line_id);
+            if let Some(lang) = language {
+                let _ = write!(attrs, " primaryLanguage=\"{}\"", lang);
+            }
+            let _ = writeln!(out, "<TextLine{}>", attrs);
+            write_coords(&mut out, line.geometry.as_ref(), dims);
+
+            for word in &line.words {
+                word_id += 1;
+                let mut attrs = format!(" id=\"word_{}\"", word_id);
+                if let Some(lang) = language {
+                    let _ = write!(attrs, " language=\"{}\"", lang);
+                }
+                let _ = writeln!(out, "<Word{}>", attrs);
+                write_coords(&mut out, word.geometry.as_ref(), dims);
+                write_text_equiv(&mut out, &word.text);
+                out.push_str("</Word>\n");
+            }
+
+            write_text_equiv(&mut out, &line.text);
+            out.push_str("</TextLine>\n");
+        }
+
+        write_text_equiv(&mut out, &paragraph.text);
+        out.push_str("</TextRegion>\n");
+    }
+
+    out.push_str("</Page>\n</PcGts>\n");
+    out
+}
+
+/// `Coords` holding the element's rotated box as four pixel points, clockwise from top-left.
+fn write_coords(out: &mut String, geometry: Option<&GeometryData>, dims: &ImageDimensions) {
+    let points = match geometry {
+        Some(g) => g
+            .corners(dims.original_width, dims.original_height)
+            .iter()
+            .map(|p| format!("{},{}", p.x.round().max(0.0), p.y.round().max(0.0)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => "0,0 0,0 0,0 0,0".to_string(),
+    };
+    let _ = writeln!(out, "<Coords points=\"{}\"/>", points);
+}
+
+fn write_text_equiv(out: &mut String, text: &str) {
+    let _ = writeln!(
+        out,
+        "<TextEquiv>\n<Unicode>{}</Unicode>\n</TextEquiv>",
+        escape_xml(text)
+    );
+}
+
+/// The PAGE language name for a BCP-47 code, for the languages the schema enumerates that are
+/// common in OCR output.
+fn language_name(code: &str) -> Option<&'static str> {
+    let primary = code.split(['-', '_']).next()?.to_ascii_lowercase();
+    let name = match primary.as_str() {
+        "ar" => "Arabic",
+        "bg" => "Bulgarian",
+        "cs" => "Czech",
+        "da" => "Danish",
+        "de" => "German",
+        "el" => "Greek",
+        "en" => "English",
+        "es" => "Spanish",
+        "fa" => "Persian",
+        "fi" => "Finnish",
+        "fr" => "French",
+        "he" | "iw" => "Hebrew",
+        "hi" => "Hindi",
+        "hu" => "Hungarian",
+        "id" => "Indonesian",
+        "it" => "Italian",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        "la" => "Latin",
+        "nl" => "Dutch",
+        "no" | "nb" | "nn" => "Norwegian",
+        "pl" => "Polish",
+        "pt" => "Portuguese",
+        "ro" => "Romanian",
+        "ru" => "Russian",
+        "sv" => "Swedish",
+        "th" => "Thai",
+        "tr" => "Turkish",
+        "uk" => "Ukrainian",
+        "vi" => "Vietnamese",
+        "zh" => "Chinese",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The current UTC time as an `xsd:dateTime`.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use roxmltree::{Document, Node};
+
+    use super::to_page_xml;
+    use crate::export::tests::fixture;
+
+    fn names(node: Node) -> Vec<String> {
+        node.children()
+            .filter(Node::is_element)
+            .map(|n| n.tag_name().name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn elements_follow_schema_order() {
+        let xml = to_page_xml(&fixture(), Some("page.png"));
+        let doc = Document::parse(&xml).unwrap();
+
+        for region in doc.descendants().filter(|n| n.has_tag_name("TextRegion")) {
+            let children = names(region);
+            assert_eq!(children.first().map(String::as_str), Some("Coords"));
+            assert_eq!(children.last().map(String::as_str), Some("TextEquiv"));
+            assert!(
+                children[1..children.len() - 1]
+                    .iter()
+                    .all(|n| n == "TextLine")
+            );
+        }
+        for line in doc.descendants().filter(|n| n.has_tag_name("TextLine")) {
+            let children = names(line);
+            assert_eq!(children.first().map(String::as_str), Some("Coords"));
+            assert_eq!(children.last().map(String::as_str), Some("TextEquiv"));
+            assert!(children[1..children.len() - 1].iter().all(|n| n == "Word"));
+        }
+        for word in doc.descendants().filter(|n| n.has_tag_name("Word")) {
+            assert_eq!(names(word), ["Coords", "TextEquiv"]);
+        }
+    }
+
+    #[test]
+    fn coords_are_four_non_negative_points() {
+        let xml = to_page_xml(&fixture(), None);
+        let doc = Document::parse(&xml).unwrap();
+
+        for coords in doc.descendants().filter(|n| n.has_tag_name("Coords")) {
+            let points: Vec<_> = coords.attribute("points").unwrap().split(' ').collect();
+            assert_eq!(points.len(), 4);
+            for point in points {
+                let (x, y) = point.split_once(',').unwrap();
+                assert!(
+                    x.parse::<u32>().is_ok() && y.parse::<u32>().is_ok(),
+                    "{}",
+                    point
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reading_order_references_regions() {
+        let xml = to_page_xml(&fixture(), None);
+        let doc = Document::parse(&xml).unwrap();
+
+        let regions: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TextRegion"))
+            .map(|n| n.attribute("id").unwrap())
+            .collect();
+        let refs: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("RegionRefIndexed"))
+            .map(|n| n.attribute("regionRef").unwrap())
+            .collect();
+        assert_eq!(refs, regions);
+    }
+
+    #[test]
+    fn languages_and_text_are_mapped() {
+        let xml = to_page_xml(&fixture(), None);
+        let doc = Document::parse(&xml).unwrap();
+
+        let regions: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("TextRegion"))
+            .collect();
+        assert_eq!(regions[0].attribute("primaryLanguage"), Some("Japanese"));
+        assert_eq!(regions[0].attribute("orientation"), Some("-10.00"));
+        assert_eq!(regions[1].attribute("primaryLanguage"), Some("English"));
+
+        let words: Vec<_> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("Word"))
+            .map(|n| n.descendants().find(|d| d.has_tag_name("Unicode")).unwrap())
+            .map(|n| n.text().unwrap_or_default())
+            .collect();
+        assert_eq!(words, ["<a>", "b&c", "d", "e"]);
+    }
+}
@@ -0,0 +1,386 @@
+//! Searchable PDF output: each page is the scanned image with an invisible, selectable text layer.
+
+use std::{fmt::Write, io::Cursor};
+
+use image::{DynamicImage, GenericImageView, codecs::jpeg::JpegEncoder};
+use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+use super::counter_clockwise_degrees;
+use crate::{
+    GeometryData, LensError, LensResult, Result,
+    constants::{DEFAULT_PDF_DPI, DEFAULT_PDF_JPEG_QUALITY},
+};
+
+const FONT_NAME: &str = "GlyphLessFont";
+
+/// How page images are compressed inside the PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfImageEncoding {
+    /// Lossy JPEG at the given quality (1-100).
+    Jpeg { quality: u8 },
+    /// Lossless Flate-compressed pixels.
+    Flate,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PdfOptions {
+    /// Resolution the page images are assumed to have; sets the physical page size.
+    pub dpi: f32,
+    pub image_encoding: PdfImageEncoding,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            dpi: DEFAULT_PDF_DPI,
+            image_encoding: PdfImageEncoding::Jpeg {
+                quality: DEFAULT_PDF_JPEG_QUALITY,
+            },
+        }
+    }
+}
+
+/// One PDF page: the image OCR ran on and its result. Pages without a result are image-only.
+#[derive(Debug, Clone, Copy)]
+pub struct PdfPage<'a> {
+    pub image: &'a DynamicImage,
+    pub result: Option<&'a LensResult>,
+}
+
+/// Builds a PDF with one page per entry, overlaying each word as invisible text positioned and
+/// rotated to match its box so the page can be searched and copied from.
+pub fn to_searchable_pdf(pages: &[PdfPage<'_>], options: &PdfOptions) -> Result<Vec<u8>> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = add_font(&mut doc);
+    let scale = 72.0 / options.dpi.max(1.0);
+
+    let mut kids = Vec::with_capacity(pages.len());
+    for page in pages {
+        let (width, height) = page.image.dimensions();
+        let (page_width, page_height) = (width as f32 * scale, height as f32 * scale);
+        let image_id = doc.add_object(image_stream(page.image, options.image_encoding)?);
+
+        let mut content = format!(
+            "q {:.3} 0 0 {:.3} 0 0 cm /Im0 Do Q\n",
+            page_width, page_height
+        );
+        if let Some(result) = page.result {
+            content.push_str("3 Tr\n");
+            write_text_layer(&mut content, result, width, height, scale);
+        }
+        let mut content = Stream::new(dictionary! {}, content.into_bytes());
+        // Compression only fails for already-filtered streams, which this is not.
+        let _ = content.compress();
+        let content_id = doc.add_object(content);
+
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+                "Font" => dictionary! { "F1" => font_id },
+            },
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut out = Vec::new();
+    doc.save_to(&mut out)?;
+    Ok(out)
+}
+
+fn image_stream(img: &DynamicImage, encoding: PdfImageEncoding) -> Result<Stream> {
+    let gray = img.color().channel_count() <= 2;
+    let (width, height) = img.dimensions();
+    let pixels = if gray {
+        img.to_luma8().into_raw()
+    } else {
+        img.to_rgb8().into_raw()
+    };
+    let color_space = if gray { "DeviceGray" } else { "DeviceRGB" };
+    let color_type = if gray {
+        image::ExtendedColorType::L8
+    } else {
+        image::ExtendedColorType::Rgb8
+    };
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width as i64,
+        "Height" => height as i64,
+        "ColorSpace" => color_space,
+        "BitsPerComponent" => 8,
+    };
+
+    match encoding {
+        PdfImageEncoding::Jpeg { quality } => {
+            let mut jpeg = Cursor::new(Vec::new());
+            JpegEncoder::new_with_quality(&mut jpeg, quality.clamp(1, 100))
+                .encode(&pixels, width, height, color_type)
+                .map_err(LensError::ImageEncode)?;
+            dict.set("Filter", "DCTDecode");
+            Ok(Stream::new(dict, jpeg.into_inner()).with_compression(false))
+        }
+        PdfImageEncoding::Flate => {
+            let mut stream = Stream::new(dict, pixels);
+            let _ = stream.compress();
+            Ok(stream)
+        }
+    }
+}
+
+fn write_text_layer(out: &mut String, result: &LensResult, width: u32, height: u32, scale: f32) {
+    for paragraph in &result.paragraphs {
+        for line in &paragraph.lines {
+            if line.words.is_empty() {
+                if let Some(g) = &line.geometry {
+                    write_text(out, &line.text, g, width, height, scale);
+                }
+                continue;
+            }
+
+            for word in &line.words {
+                if let Some(g) = &word.geometry {
+                    let text = format!("{}{}", word.text, word.separator);
+                    write_text(out, &text, g, width, height, scale);
+                }
+            }
+        }
+    }
+}
+
+/// Writes `text` stretched along the box `g`. Boxes taller than wide holding more than one
+/// character are treated as vertical text running top to bottom.
+fn write_text(out: &mut String, text: &str, g: &GeometryData, width: u32, height: u32, scale: f32) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let units: Vec<u16> = text.chars().map(code_unit).collect();
+
+    let px = g.to_pixels(width, height);
+    let [top_left, _, _, bottom_left] = g.corners(width, height);
+    let vertical = px.height > px.width && text.trim().chars().count() > 1;
+    let angle = counter_clockwise_degrees(g);
+    // Vertical runs are turned a further quarter turn clockwise to read top to bottom.
+    let (origin, angle, length, size) = if vertical {
+        (top_left, angle - 90.0, px.height, px.width)
+    } else {
+        (bottom_left, angle, px.width, px.height)
+    };
+    if length <= 0.0 || size <= 0.0 {
+        return;
+    }
+
+    // Every glyph is one em wide (DW 1000), so Tz stretches the run to exactly fill the box.
+    let horizontal_scale = 100.0 * length / (units.len() as f32 * size);
+    // PDF's y axis points up, so the counter-clockwise angle is used as is.
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let hex: String = units.iter().map(|u| format!("{:04X}", u)).collect();
+
+    let _ = writeln!(
+        out,
+        "BT /F1 {:.3} Tf {:.3} Tz {:.5} {:.5} {:.5} {:.5} {:.3} {:.3} Tm <{}> Tj ET",
+        size * scale,
+        horizontal_scale,
+        cos,
+        sin,
+        -sin,
+        cos,
+        origin.x * scale,
+        (height as f32 - origin.y) * scale,
+        hex
+    );
+}
+
+/// The CID for a character: its UTF-16 code unit, or U+FFFD outside the Basic Multilingual Plane.
+fn code_unit(c: char) -> u16 {
+    u16::try_from(c as u32).unwrap_or(0xFFFD)
+}
+
+/// Adds a Type0 font whose CIDs are UTF-16 code units, mapped back to Unicode by a ToUnicode
+/// CMap. No glyphs are embedded since the text is never drawn.
+fn add_font(doc: &mut Document) -> ObjectId {
+    let mut to_unicode = Stream::new(dictionary! {}, to_unicode_cmap().into_bytes());
+    let _ = to_unicode.compress();
+    let to_unicode = doc.add_object(to_unicode);
+    let descriptor = doc.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => FONT_NAME,
+        "Flags" => 5,
+        "FontBBox" => vec![0.into(), 0.into(), 1000.into(), 1000.into()],
+        "ItalicAngle" => 0,
+        "Ascent" => 1000,
+        "Descent" => 0,
+        "CapHeight" => 1000,
+        "StemV" => 80,
+    });
+    let cid_font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType2",
+        "BaseFont" => FONT_NAME,
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Identity"),
+            "Supplement" => 0,
+        },
+        "FontDescriptor" => descriptor,
+        "DW" => 1000,
+        "CIDToGIDMap" => "Identity",
+    });
+
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => FONT_NAME,
+        "Encoding" => "Identity-H",
+        "DescendantFonts" => vec![Object::Reference(cid_font)],
+        "ToUnicode" => to_unicode,
+    })
+}
+
+fn to_unicode_cmap() -> String {
+    // Surrogate code units never occur because `code_unit` replaces non-BMP characters.
+    let ranges: Vec<u16> = (0x00..=0xFF)
+        .filter(|hi| !(0xD8..=0xDF).contains(hi))
+        .collect();
+
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    // bfrange blocks are limited to 100 entries.
+    for chunk in ranges.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfrange", chunk.len());
+        for hi in chunk {
+            let _ = writeln!(cmap, "<{0:02X}00> <{0:02X}FF> <{0:02X}00>", hi);
+        }
+        cmap.push_str("endbfrange\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::content::Content;
+
+    use super::*;
+    use crate::export::tests::fixture;
+
+    /// The fixture's 800x600 page with its text layer, then the same image without one.
+    fn parsed_pdf() -> Document {
+        let image = DynamicImage::new_rgb8(800, 600);
+        let result = fixture();
+        let pages = [
+            PdfPage {
+                image: &image,
+                result: Some(&result),
+            },
+            PdfPage {
+                image: &image,
+                result: None,
+            },
+        ];
+        let options = PdfOptions {
+            dpi: 144.0,
+            ..Default::default()
+        };
+        Document::load_mem(&to_searchable_pdf(&pages, &options).unwrap()).unwrap()
+    }
+
+    fn operations(doc: &Document, page_id: ObjectId, operator: &str) -> Vec<Vec<f32>> {
+        let content = Content::decode(&doc.get_page_content(page_id).unwrap()).unwrap();
+        content
+            .operations
+            .into_iter()
+            .filter(|op| op.operator == operator)
+            .map(|op| {
+                op.operands
+                    .iter()
+                    .filter_map(|o| o.as_float().ok())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pages_are_sized_from_the_dpi() {
+        let doc = parsed_pdf();
+        let pages: Vec<_> = doc.get_pages().into_values().collect();
+        assert_eq!(pages.len(), 2);
+
+        for page_id in pages {
+            let media_box: Vec<f32> = doc
+                .get_dictionary(page_id)
+                .unwrap()
+                .get(b"MediaBox")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_float().unwrap())
+                .collect();
+            assert_eq!(media_box, [0.0, 0.0, 400.0, 300.0]);
+        }
+    }
+
+    #[test]
+    fn each_placed_word_is_one_rotated_run() {
+        let doc = parsed_pdf();
+        let pages: Vec<_> = doc.get_pages().into_values().collect();
+
+        // Two of the fixture's words have boxes, both rotated 10 degrees clockwise.
+        let runs = operations(&doc, pages[0], "Tj");
+        assert_eq!(runs.len(), 2);
+        let matrices = operations(&doc, pages[0], "Tm");
+        assert_eq!(matrices.len(), 2);
+        let (sin, cos) = 10f32.to_radians().sin_cos();
+        for tm in matrices {
+            for (actual, expected) in tm[..4].iter().zip([cos, -sin, sin, cos]) {
+                assert!((actual - expected).abs() < 1e-4, "{:?}", tm);
+            }
+        }
+
+        assert!(operations(&doc, pages[1], "Tj").is_empty());
+    }
+
+    #[test]
+    fn font_maps_back_to_unicode() {
+        let doc = parsed_pdf();
+        let page_id = doc.get_pages()[&1];
+        let fonts = doc.get_page_fonts(page_id).unwrap();
+        let font = fonts[b"F1".as_slice()];
+
+        let to_unicode = font
+            .get_deref(b"ToUnicode", &doc)
+            .unwrap()
+            .as_stream()
+            .unwrap()
+            .decompressed_content()
+            .unwrap();
+        let cmap = String::from_utf8(to_unicode).unwrap();
+        assert!(cmap.contains("beginbfrange"), "{}", cmap);
+        assert!(cmap.contains("<0000> <00FF> <0000>"), "{}", cmap);
+    }
+}
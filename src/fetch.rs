@@ -0,0 +1,209 @@
+//! Downloading images over HTTP(S) for OCR.
+
+use reqwest::{
+    Response,
+    header::{CONTENT_TYPE, USER_AGENT},
+};
+
+use crate::{LensClient, LensError, LensResult, Result, constants::MAX_ERROR_BODY_BYTES};
+
+impl LensClient {
+    /// Downloads an image with this client's proxy and timeout settings, then OCRs it.
+    ///
+    /// The response must be at most the builder's `max_download_bytes` and have an `image/*`
+    /// content type; when the server omits the content type, the bytes must look like an image.
+    pub async fn process_image_url(&self, url: &str, lang: Option<&str>) -> Result<LensResult> {
+        let data = self.download_image(url).await?;
+        self.process_encoded(data, lang).await
+    }
+
+    /// Downloads an image with the same checks as [`process_image_url`](Self::process_image_url).
+    pub async fn download_image(&self, url: &str) -> Result<Vec<u8>> {
+        let parsed =
+            url::Url::parse(url).map_err(|e| LensError::InvalidUrl(format!("{}: {}", url, e)))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(LensError::InvalidUrl(format!(
+                "{}: only http and https are supported",
+                url
+            )));
+        }
+
+        let mut request = self.client.get(parsed);
+        if let Some(user_agent) = self.headers.get(USER_AGENT) {
+            request = request.header(USER_AGENT, user_agent);
+        }
+        let mut response = request.send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = read_error_body(response).await;
+            return Err(LensError::Http {
+                status,
+                body,
+                retry_after: None,
+            });
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+        if let Some(content_type) = &content_type
+            && !content_type.starts_with("image/")
+        {
+            return Err(LensError::UnsupportedContentType(content_type.clone()));
+        }
+
+        let limit = self.max_download_bytes;
+        if response.content_length().is_some_and(|len| len > limit) {
+            return Err(LensError::DownloadTooLarge { limit });
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() as u64 + chunk.len() as u64 > limit {
+                return Err(LensError::DownloadTooLarge { limit });
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        if content_type.is_none() && image::guess_format(&data).is_err() {
+            return Err(LensError::UnsupportedContentType(
+                "unknown (no Content-Type and unrecognized data)".to_string(),
+            ));
+        }
+
+        Ok(data)
+    }
+}
+
+/// Reads at most [`MAX_ERROR_BODY_BYTES`] of an error response, which is only kept as context and
+/// may be arbitrarily large.
+async fn read_error_body(mut response: Response) -> String {
+    let mut body = Vec::new();
+    while body.len() < MAX_ERROR_BODY_BYTES
+        && let Ok(Some(chunk)) = response.chunk().await
+    {
+        body.extend_from_slice(&chunk);
+    }
+    body.truncate(MAX_ERROR_BODY_BYTES);
+    String::from_utf8_lossy(&body).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serves `response` verbatim to one connection and returns the URL to request.
+    async fn serve(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            // The client may hang up early once it has seen enough.
+            let _ = socket.write_all(&response).await;
+            let _ = socket.shutdown().await;
+        });
+        format!("http://{}/image.png", addr)
+    }
+
+    fn client(max_download_bytes: u64) -> LensClient {
+        LensClient::builder()
+            .max_download_bytes(max_download_bytes)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn non_image_content_types_are_rejected() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 6\r\n\r\n<html>"
+                .to_vec(),
+        )
+        .await;
+        let err = client(1024).download_image(&url).await.unwrap_err();
+        assert!(
+            matches!(&err, LensError::UnsupportedContentType(t) if t == "text/html"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_is_rejected() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 100\r\n\r\n".to_vec(),
+        )
+        .await;
+        let err = client(10).download_image(&url).await.unwrap_err();
+        assert!(
+            matches!(err, LensError::DownloadTooLarge { limit: 10 }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_chunked_bodies_are_rejected() {
+        let mut response =
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nTransfer-Encoding: chunked\r\n\r\n"
+                .to_vec();
+        for _ in 0..4 {
+            response.extend_from_slice(b"4\r\nabcd\r\n");
+        }
+        response.extend_from_slice(b"0\r\n\r\n");
+        let url = serve(response).await;
+
+        let err = client(10).download_image(&url).await.unwrap_err();
+        assert!(
+            matches!(err, LensError::DownloadTooLarge { limit: 10 }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn only_http_schemes_are_fetched() {
+        for url in ["ftp://example.com/image.png", "file:///etc/passwd"] {
+            let err = client(1024).download_image(url).await.unwrap_err();
+            assert!(matches!(err, LensError::InvalidUrl(_)), "{:?}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_bodies_are_capped() {
+        let body = "x".repeat(MAX_ERROR_BODY_BYTES * 4);
+        let url = serve(
+            format!(
+                "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .into_bytes(),
+        )
+        .await;
+
+        let err = client(1024).download_image(&url).await.unwrap_err();
+        match err {
+            LensError::Http { status, body, .. } => {
+                assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+                assert_eq!(body.len(), MAX_ERROR_BODY_BYTES);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
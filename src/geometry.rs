@@ -0,0 +1,133 @@
+//! Conversions from the server's normalized boxes to pixel coordinates.
+//!
+//! [`GeometryData`] values are fractions of the uploaded image. Because resizing preserves the
+//! aspect ratio, multiplying by the *original* image's dimensions maps them onto the source page
+//! regardless of how far [`image_processor`](crate::image_processor) scaled it down.
+
+use crate::{GeometryData, LensResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// An axis-aligned rectangle with its origin at the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+}
+
+impl GeometryData {
+    /// Returns this box scaled to an image of `image_width` x `image_height` pixels.
+    pub fn to_pixels(&self, image_width: u32, image_height: u32) -> GeometryData {
+        let (w, h) = (image_width as f32, image_height as f32);
+        GeometryData {
+            center_x: self.center_x * w,
+            center_y: self.center_y * h,
+            width: self.width * w,
+            height: self.height * h,
+            rotation_z: self.rotation_z,
+            angle_deg: self.angle_deg,
+        }
+    }
+
+    /// Maps a box normalized to `sub`, a rectangle of a `full_width` x `full_height` image, to a
+    /// box normalized to the full image.
+    pub fn map_from_subimage(&self, sub: Rect, full_width: u32, full_height: u32) -> GeometryData {
+        let (w, h) = (full_width as f32, full_height as f32);
+
+        GeometryData {
+            center_x: (sub.x + self.center_x * sub.width) / w,
+            center_y: (sub.y + self.center_y * sub.height) / h,
+            width: self.width * sub.width / w,
+            height: self.height * sub.height / h,
+            rotation_z: self.rotation_z,
+            angle_deg: self.angle_deg,
+        }
+    }
+
+    /// The four corners of the rotated box in pixels, clockwise from the top-left corner of the
+    /// unrotated box.
+    pub fn corners(&self, image_width: u32, image_height: u32) -> [Point; 4] {
+        let px = self.to_pixels(image_width, image_height);
+        let (half_w, half_h) = (px.width / 2.0, px.height / 2.0);
+        let (sin, cos) = px.rotation_z.sin_cos();
+
+        [
+            (-half_w, -half_h),
+            (half_w, -half_h),
+            (half_w, half_h),
+            (-half_w, half_h),
+        ]
+        .map(|(dx, dy)| Point {
+            x: px.center_x + dx * cos - dy * sin,
+            y: px.center_y + dx * sin + dy * cos,
+        })
+    }
+
+    /// The smallest axis-aligned rectangle in pixels that contains the rotated box.
+    pub fn bounding_rect(&self, image_width: u32, image_height: u32) -> Rect {
+        let corners = self.corners(image_width, image_height);
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for p in corners {
+            min_x = min_x.min(p.x);
+            min_y = min_y.min(p.y);
+            max_x = max_x.max(p.x);
+            max_y = max_y.max(p.y);
+        }
+
+        Rect {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        }
+    }
+}
+
+impl LensResult {
+    /// Replaces every paragraph, line and word box with `f(box)`.
+    pub fn map_geometry(&mut self, f: impl Fn(&GeometryData) -> GeometryData) {
+        let map = |g: &mut Option<GeometryData>| {
+            if let Some(geometry) = g {
+                *geometry = f(geometry);
+            }
+        };
+
+        for paragraph in &mut self.paragraphs {
+            map(&mut paragraph.geometry);
+            for line in &mut paragraph.lines {
+                map(&mut line.geometry);
+                for word in &mut line.words {
+                    map(&mut word.geometry);
+                }
+            }
+        }
+    }
+}
@@ -1,56 +1,466 @@
-use std::{io::Cursor, path::Path};
+use std::{
+    io::{BufRead, Cursor, Seek},
+    path::Path,
+};
 
-use anyhow::{Context, Result};
-use image::{ImageFormat, ImageReader};
+pub use image::imageops::FilterType;
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, RgbaImage,
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    metadata::Orientation,
+};
 
-use crate::constants::DEFAULT_IMAGE_MAX_DIMENSION;
+use crate::{
+    constants::DEFAULT_IMAGE_MAX_DIMENSION,
+    error::{LensError, Result},
+    preprocess::{self, PreprocessStep},
+};
 
 pub struct ProcessedImage {
     pub bytes: Vec<u8>,
+    /// Width of the encoded image that is uploaded.
     pub width: i32,
+    /// Height of the encoded image that is uploaded.
     pub height: i32,
+    /// Width of the decoded input before resizing.
+    pub original_width: u32,
+    /// Height of the decoded input before resizing.
+    pub original_height: u32,
+    /// Format of `bytes`.
+    pub format: ImageFormat,
+}
+
+impl ProcessedImage {
+    /// Size of the upload payload in bytes.
+    pub fn byte_size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn dimensions(&self) -> ImageDimensions {
+        let sent_width = self.width.max(0) as u32;
+        let sent_height = self.height.max(0) as u32;
+        let scale_factor = if self.original_width == 0 {
+            1.0
+        } else {
+            sent_width as f32 / self.original_width as f32
+        };
+
+        ImageDimensions {
+            original_width: self.original_width,
+            original_height: self.original_height,
+            sent_width,
+            sent_height,
+            scale_factor,
+        }
+    }
+}
+
+/// Sizes of the image before and after resizing for upload.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageDimensions {
+    pub original_width: u32,
+    pub original_height: u32,
+    pub sent_width: u32,
+    pub sent_height: u32,
+    /// `sent_width / original_width`; `1.0` when the image was not resized.
+    pub scale_factor: f32,
+}
+
+/// Channel order of a [`RawImage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8,
+    /// Common for screen captures on Windows and macOS.
+    Bgra8,
+}
+
+/// An uncompressed 4-bytes-per-pixel buffer, such as a captured screen frame.
+#[derive(Debug, Clone, Copy)]
+pub struct RawImage<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row, including any padding. At least `width * 4`.
+    pub stride: usize,
+    pub format: PixelFormat,
+}
+
+impl RawImage<'_> {
+    /// Copies the buffer into an RGBA image, dropping row padding.
+    pub fn to_image(&self) -> Result<DynamicImage> {
+        let too_large = || {
+            LensError::InvalidBuffer(format!(
+                "{}x{} with stride {} does not fit in memory",
+                self.width, self.height, self.stride
+            ))
+        };
+        let row_len = (self.width as usize).checked_mul(4).ok_or_else(too_large)?;
+        if self.stride < row_len {
+            return Err(LensError::InvalidBuffer(format!(
+                "stride {} is less than width * 4 ({})",
+                self.stride, row_len
+            )));
+        }
+        let needed = match self.height {
+            0 => 0,
+            h => self
+                .stride
+                .checked_mul(h as usize - 1)
+                .and_then(|n| n.checked_add(row_len))
+                .ok_or_else(too_large)?,
+        };
+        if self.data.len() < needed {
+            return Err(LensError::InvalidBuffer(format!(
+                "{} bytes given, {} needed for {}x{} with stride {}",
+                self.data.len(),
+                needed,
+                self.width,
+                self.height,
+                self.stride
+            )));
+        }
+
+        let mut pixels = Vec::with_capacity(row_len * self.height as usize);
+        for row in 0..self.height as usize {
+            let start = row * self.stride;
+            pixels.extend_from_slice(&self.data[start..start + row_len]);
+        }
+        if self.format == PixelFormat::Bgra8 {
+            pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+        }
+
+        let buffer = RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("buffer length matches dimensions");
+        Ok(DynamicImage::ImageRgba8(buffer))
+    }
+}
+
+/// How images are resized before upload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeMode {
+    /// Upload at the original size.
+    None,
+    /// Downscale so neither side exceeds this many pixels.
+    MaxDimension(u32),
+    /// Downscale so `width * height` does not exceed this many pixels.
+    MaxPixels(u64),
+}
+
+/// How images are encoded for upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadEncoding {
+    Png,
+    /// JPEG with `quality` in `1..=100`. Transparency is flattened.
+    Jpeg {
+        quality: u8,
+    },
+    WebpLossless,
+    /// Upload the caller's original bytes unchanged when they are PNG, JPEG or WebP, the image
+    /// did not need resizing, and they are at most `max_bytes` long. Otherwise falls back to PNG.
+    Passthrough {
+        max_bytes: usize,
+    },
+}
+
+/// Controls how [`process_image_from_path_with_options`] and friends prepare an image.
+#[derive(Debug, Clone)]
+pub struct ImageProcessingOptions {
+    pub resize: ResizeMode,
+    /// Resampling filter used when downscaling.
+    pub filter: FilterType,
+    pub encoding: UploadEncoding,
+    /// Clean-up steps applied after resizing and before encoding.
+    pub preprocess: Vec<PreprocessStep>,
+}
+
+impl Default for ImageProcessingOptions {
+    fn default() -> Self {
+        Self {
+            resize: ResizeMode::MaxDimension(DEFAULT_IMAGE_MAX_DIMENSION),
+            filter: FilterType::Lanczos3,
+            encoding: UploadEncoding::Png,
+            preprocess: Vec::new(),
+        }
+    }
+}
+
+impl ImageProcessingOptions {
+    /// The size an image of `width` x `height` is resized to, or `None` if it fits as-is.
+    pub fn target_size(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        match self.resize {
+            ResizeMode::None => None,
+            ResizeMode::MaxDimension(max) => {
+                let max = max.max(1);
+                if width <= max && height <= max {
+                    return None;
+                }
+                // The longer side becomes exactly `max`; the other keeps the aspect ratio.
+                let (long, short) = (width.max(height) as u64, width.min(height) as u64);
+                let short = ((short * max as u64 + long / 2) / long).clamp(1, max as u64) as u32;
+                Some(if width >= height {
+                    (max, short)
+                } else {
+                    (short, max)
+                })
+            }
+            ResizeMode::MaxPixels(max) => {
+                let pixels = width as u64 * height as u64;
+                if pixels <= max {
+                    return None;
+                }
+                // Rounding down keeps the result within the pixel budget.
+                let scale = (max as f64 / pixels as f64).sqrt();
+                Some((
+                    ((width as f64 * scale).floor() as u32).max(1),
+                    ((height as f64 * scale).floor() as u32).max(1),
+                ))
+            }
+        }
+    }
 }
 
 pub fn process_image_from_path<P: AsRef<Path>>(path: P) -> Result<ProcessedImage> {
-    let img = ImageReader::open(path)
-        .context("Failed to open image file")?
-        .with_guessed_format()
-        .context("Failed to guess image format")?
-        .decode()
-        .context("Failed to decode image")?;
+    process_image_from_path_with_options(path, &ImageProcessingOptions::default())
+}
 
-    process_image_internal(img)
+pub fn process_image_from_path_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ImageProcessingOptions,
+) -> Result<ProcessedImage> {
+    let data = std::fs::read(path)?;
+    process_image_from_bytes_with_options(&data, options)
 }
 
 pub fn process_image_from_bytes(data: &[u8]) -> Result<ProcessedImage> {
-    let img = image::load_from_memory(data).context("Failed to load image from memory")?;
-    process_image_internal(img)
+    process_image_from_bytes_with_options(data, &ImageProcessingOptions::default())
+}
+
+pub fn process_image_from_bytes_with_options(
+    data: &[u8],
+    options: &ImageProcessingOptions,
+) -> Result<ProcessedImage> {
+    process_image_internal(load_image_from_bytes(data)?, Some(data), options)
+}
+
+pub fn process_image(img: DynamicImage) -> Result<ProcessedImage> {
+    process_image_with_options(img, &ImageProcessingOptions::default())
+}
+
+pub fn process_image_with_options(
+    img: DynamicImage,
+    options: &ImageProcessingOptions,
+) -> Result<ProcessedImage> {
+    process_image_internal(img, None, options)
+}
+
+/// Like [`process_image_with_options`], for an image decoded from `original_bytes`, which
+/// [`UploadEncoding::Passthrough`] may upload unchanged.
+pub fn process_decoded_image(
+    img: DynamicImage,
+    original_bytes: Option<&[u8]>,
+    options: &ImageProcessingOptions,
+) -> Result<ProcessedImage> {
+    process_image_internal(img, original_bytes, options)
+}
+
+/// Decodes an image file without resizing or re-encoding it. EXIF orientation is applied, so the
+/// result is oriented the way image viewers display it.
+pub fn load_image_from_path<P: AsRef<Path>>(path: P) -> Result<DynamicImage> {
+    decode_oriented(ImageReader::open(path)?.with_guessed_format()?)
+}
+
+/// Decodes an encoded image without resizing or re-encoding it. EXIF orientation is applied, so
+/// the result is oriented the way image viewers display it.
+pub fn load_image_from_bytes(data: &[u8]) -> Result<DynamicImage> {
+    decode_oriented(ImageReader::new(Cursor::new(data)).with_guessed_format()?)
+}
+
+fn decode_oriented<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage> {
+    let mut decoder = reader.into_decoder().map_err(LensError::ImageDecode)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(LensError::ImageDecode)?;
+    img.apply_orientation(orientation);
+    Ok(img)
 }
 
-fn process_image_internal(mut img: image::DynamicImage) -> Result<ProcessedImage> {
-    // Resize if larger than max dimension
+/// Whether `data` carries an EXIF orientation other than the identity.
+fn has_orientation_transform(data: &[u8]) -> bool {
+    ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .is_some_and(|orientation| orientation != Orientation::NoTransforms)
+}
+
+fn process_image_internal(
+    mut img: DynamicImage,
+    original_bytes: Option<&[u8]>,
+    options: &ImageProcessingOptions,
+) -> Result<ProcessedImage> {
     let (w, h) = (img.width(), img.height());
-    if w > DEFAULT_IMAGE_MAX_DIMENSION || h > DEFAULT_IMAGE_MAX_DIMENSION {
-        img = img.resize(
-            DEFAULT_IMAGE_MAX_DIMENSION,
-            DEFAULT_IMAGE_MAX_DIMENSION,
-            image::imageops::FilterType::Lanczos3,
-        );
+    let mut modified = false;
+    if let Some((target_w, target_h)) = options.target_size(w, h) {
+        img = img.resize_exact(target_w, target_h, options.filter);
+        modified = true;
     }
 
-    let (final_w, final_h) = (img.width() as i32, img.height() as i32);
+    if !options.preprocess.is_empty() {
+        img = preprocess::apply(img, &options.preprocess);
+        modified = true;
+    }
 
-    // Convert to RGBA and save as PNG bytes
-    let mut bytes: Vec<u8> = Vec::new();
-    let mut cursor = Cursor::new(&mut bytes);
+    let (final_w, final_h) = (img.width() as i32, img.height() as i32);
 
-    // Lens expects PNG (or supported formats), mapping Python's logic
-    img.write_to(&mut cursor, ImageFormat::Png)
-        .context("Failed to write image to buffer")?;
+    let (bytes, format) = match (options.encoding, original_bytes) {
+        (UploadEncoding::Passthrough { max_bytes }, Some(original))
+            if !modified && original.len() <= max_bytes && !has_orientation_transform(original) =>
+        {
+            match image::guess_format(original) {
+                Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) => {
+                    (original.to_vec(), format)
+                }
+                _ => encode(&img, UploadEncoding::Png)?,
+            }
+        }
+        (encoding, _) => encode(&img, encoding)?,
+    };
 
     Ok(ProcessedImage {
         bytes,
         width: final_w,
         height: final_h,
+        original_width: w,
+        original_height: h,
+        format,
     })
 }
+
+fn encode(img: &DynamicImage, encoding: UploadEncoding) -> Result<(Vec<u8>, ImageFormat)> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut cursor = Cursor::new(&mut bytes);
+
+    let format = match encoding {
+        UploadEncoding::Png | UploadEncoding::Passthrough { .. } => {
+            // Lens expects PNG (or supported formats), mapping Python's logic
+            img.write_to(&mut cursor, ImageFormat::Png)
+                .map_err(LensError::ImageEncode)?;
+            ImageFormat::Png
+        }
+        UploadEncoding::Jpeg { quality } => {
+            let encoder = JpegEncoder::new_with_quality(&mut cursor, quality.clamp(1, 100));
+            DynamicImage::ImageRgb8(img.to_rgb8())
+                .write_with_encoder(encoder)
+                .map_err(LensError::ImageEncode)?;
+            ImageFormat::Jpeg
+        }
+        UploadEncoding::WebpLossless => {
+            let encoder = WebPEncoder::new_lossless(&mut cursor);
+            DynamicImage::ImageRgba8(img.to_rgba8())
+                .write_with_encoder(encoder)
+                .map_err(LensError::ImageEncode)?;
+            ImageFormat::WebP
+        }
+    };
+
+    Ok((bytes, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(resize: ResizeMode) -> ImageProcessingOptions {
+        ImageProcessingOptions {
+            resize,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn max_dimension_sets_the_long_side_exactly() {
+        let options = options(ResizeMode::MaxDimension(1500));
+
+        assert_eq!(options.target_size(1000, 2147), Some((699, 1500)));
+        assert_eq!(options.target_size(2147, 1000), Some((1500, 699)));
+        assert_eq!(options.target_size(3000, 3000), Some((1500, 1500)));
+        assert_eq!(options.target_size(100_000, 10), Some((1500, 1)));
+        assert_eq!(options.target_size(1500, 1499), None);
+    }
+
+    #[test]
+    fn max_pixels_stays_within_budget() {
+        let options = options(ResizeMode::MaxPixels(1_000_000));
+
+        for (width, height) in [(4000, 3000), (1001, 1000), (2147, 1000), (50_000, 21)] {
+            let (w, h) = options.target_size(width, height).unwrap();
+            assert!(w as u64 * h as u64 <= 1_000_000, "{}x{}", w, h);
+            assert!(w <= width && h <= height);
+        }
+        assert_eq!(options.target_size(1000, 1000), None);
+    }
+
+    fn raw(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: PixelFormat,
+    ) -> RawImage<'_> {
+        RawImage {
+            data,
+            width,
+            height,
+            stride,
+            format,
+        }
+    }
+
+    #[test]
+    fn raw_images_drop_row_padding() {
+        // 2x2 RGBA with 3 bytes of padding after each row.
+        let data = [
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, //
+            9, 10, 11, 12, 13, 14, 15, 16, 0, 0, 0,
+        ];
+        let img = raw(&data, 2, 2, 11, PixelFormat::Rgba8).to_image().unwrap();
+        assert_eq!(img.to_rgba8().as_raw(), &(1..=16).collect::<Vec<u8>>());
+
+        // The last row needs no padding.
+        let img = raw(&data[..19], 2, 2, 11, PixelFormat::Rgba8)
+            .to_image()
+            .unwrap();
+        assert_eq!((img.width(), img.height()), (2, 2));
+    }
+
+    #[test]
+    fn raw_bgra_is_swapped_to_rgba() {
+        let data = [10, 20, 30, 255, 1, 2, 3, 128];
+        let img = raw(&data, 2, 1, 8, PixelFormat::Bgra8).to_image().unwrap();
+        assert_eq!(img.to_rgba8().as_raw(), &[30, 20, 10, 255, 3, 2, 1, 128]);
+    }
+
+    #[test]
+    fn invalid_raw_buffers_are_rejected() {
+        let data = [0u8; 16];
+        let cases = [
+            raw(&data, 2, 2, 7, PixelFormat::Rgba8),
+            raw(&data, 2, 3, 8, PixelFormat::Rgba8),
+            raw(&data, 2, 3, usize::MAX / 2, PixelFormat::Rgba8),
+            raw(&data, u32::MAX, 2, usize::MAX, PixelFormat::Bgra8),
+        ];
+        for image in cases {
+            assert!(
+                matches!(image.to_image(), Err(LensError::InvalidBuffer(_))),
+                "{:?}",
+                image
+            );
+        }
+    }
+
+    #[test]
+    fn no_resize_keeps_the_size() {
+        assert_eq!(options(ResizeMode::None).target_size(9000, 9000), None);
+    }
+}
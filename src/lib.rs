@@ -1,16 +1,59 @@
+pub mod batch;
+pub mod cache;
 pub mod constants;
+pub mod document;
+pub mod error;
+pub mod export;
+pub mod fetch;
+pub mod geometry;
 pub mod image_processor;
+pub mod orientation;
+pub mod preprocess;
 pub mod proto;
-
-use std::{f32::consts::PI, time::Duration};
-
-use anyhow::anyhow;
+pub mod rate_limit;
+pub mod reading_order;
+pub mod region;
+pub mod render;
+pub mod retry;
+#[cfg(test)]
+mod test_support;
+pub mod tiling;
+
+use std::{f32::consts::PI, sync::Arc, time::Duration};
+
+use image::DynamicImage;
 use prost::Message;
-use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
-
-use crate::{constants::*, proto::*};
-
+use reqwest::{
+    StatusCode,
+    header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER, USER_AGENT},
+};
+use tokio::sync::Semaphore;
+
+pub use crate::{
+    batch::{BatchOptions, ImageSource},
+    cache::{FileCache, MemoryCache, ResultCache},
+    document::{DocumentResult, PageResult},
+    error::{LensError, Result},
+    geometry::{Point, Rect},
+    image_processor::{
+        ImageDimensions, ImageProcessingOptions, PixelFormat, RawImage, ResizeMode, UploadEncoding,
+    },
+    orientation::Rotation,
+    preprocess::PreprocessStep,
+    rate_limit::RateLimit,
+    reading_order::ReadingOrder,
+    region::Region,
+    retry::RetryPolicy,
+    tiling::TilingOptions,
+};
+use crate::{cache::CacheKey, constants::*, proto::*, rate_limit::RateLimiter};
+
+/// The parsed OCR output for one image.
+///
+/// With the `serde` feature enabled, this and the nested result types serialize with their field
+/// names as-is; see the README for the JSON schema.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LensResult {
     /// The full text combined with newlines.
     pub full_text: String,
@@ -18,16 +61,80 @@ pub struct LensResult {
     pub paragraphs: Vec<Paragraph>,
     /// Translated text if available (requires target language).
     pub translation: Option<String>,
+    /// Detected language of the page as a BCP-47 code (e.g. `"ja"`), if the server reported one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub language: Option<String>,
+    /// Original and uploaded image sizes. Pass `original_width`/`original_height` to the
+    /// [`GeometryData`] pixel helpers to map boxes onto the source image.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub image: ImageDimensions,
+}
+
+impl LensResult {
+    /// Splits `translation` into one string per paragraph.
+    ///
+    /// The server returns a single string. When it has one line per paragraph the lines are
+    /// used as-is; otherwise lines are assigned in order by their share of the text, matched
+    /// against each paragraph's share of the source text. Paragraphs left without text get an
+    /// empty string. Without paragraphs, every non-empty line is returned.
+    pub fn paragraph_translations(&self) -> Vec<String> {
+        let count = self.paragraphs.len();
+        let Some(translation) = &self.translation else {
+            return vec![String::new(); count];
+        };
+        let lines: Vec<&str> = translation
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        if lines.len() == count || count == 0 {
+            return lines.into_iter().map(str::to_string).collect();
+        }
+
+        let weight = |s: &str| s.chars().count().max(1) as f32;
+        let source_total: f32 = self.paragraphs.iter().map(|p| weight(&p.text)).sum();
+        let mut paragraph_ends = Vec::with_capacity(count);
+        let mut cumulative = 0.0;
+        for paragraph in &self.paragraphs {
+            cumulative += weight(&paragraph.text) / source_total;
+            paragraph_ends.push(cumulative);
+        }
+
+        let line_total: f32 = lines.iter().map(|l| weight(l)).sum();
+        let mut translations = vec![String::new(); count];
+        let mut position = 0.0;
+        for line in lines {
+            let share = weight(line) / line_total;
+            let midpoint = position + share / 2.0;
+            position += share;
+
+            let index = paragraph_ends
+                .iter()
+                .position(|&end| midpoint <= end)
+                .unwrap_or(count - 1);
+            let text = &mut translations[index];
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(line);
+        }
+        translations
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Paragraph {
     pub text: String,
     pub lines: Vec<Line>,
     pub geometry: Option<GeometryData>,
+    /// Detected language of this paragraph, if the server reported one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
     pub text: String,
     pub words: Vec<Word>,
@@ -35,13 +142,17 @@ pub struct Line {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Word {
     pub text: String,
     pub separator: String,
     pub geometry: Option<GeometryData>,
 }
 
+/// A rotated box normalized to `0.0..=1.0` of the image. Use [`GeometryData::to_pixels`],
+/// [`GeometryData::corners`] or [`GeometryData::bounding_rect`] for pixel coordinates.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GeometryData {
     pub center_x: f32,
     pub center_y: f32,
@@ -53,59 +164,158 @@ pub struct GeometryData {
 
 // --- Client Implementation ---
 
+/// Cloning is cheap and clones share the same connection pool, rate limiter and in-flight limit.
+#[derive(Clone)]
 pub struct LensClient {
     client: reqwest::Client,
-    api_key: String,
+    endpoint: String,
+    headers: HeaderMap,
+    region: String,
+    time_zone: String,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    in_flight: Option<Arc<Semaphore>>,
+    cache: Option<Arc<dyn ResultCache>>,
+    image_options: ImageProcessingOptions,
+    tiling: Option<TilingOptions>,
+    rotation: Rotation,
+    auto_deskew: bool,
+    max_download_bytes: u64,
+    reading_order: ReadingOrder,
+    /// Set by [`LensClient::new`] when its configuration was invalid; fails every request.
+    config_error: Option<String>,
 }
 
 impl LensClient {
+    /// An `api_key` that is not a valid HTTP header value is reported as [`LensError::Config`]
+    /// by every request; use [`LensClient::builder`] to get the error up front.
     pub fn new(api_key: Option<String>) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .build()
-            .unwrap_or_default();
+        let mut builder = LensClientBuilder::new();
+        if let Some(key) = api_key {
+            builder = builder.api_key(key);
+        }
 
-        Self {
-            client,
-            api_key: api_key.unwrap_or_else(|| DEFAULT_API_KEY.to_string()),
+        if let Ok(client) = builder.clone().build() {
+            return client;
         }
-    }
 
-    pub fn new_with_proxy(api_key: Option<String>, proxy_url: Option<&str>) -> anyhow::Result<Self> {
-        let mut client_builder = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60));
+        // Only built when the configured client could not be, as it costs a TLS setup.
+        let fallback = builder.client(reqwest::Client::default());
+        match fallback.clone().build() {
+            Ok(client) => client,
+            Err(e) => {
+                let mut client = fallback
+                    .api_key(DEFAULT_API_KEY)
+                    .build()
+                    .expect("the default configuration is valid");
+                client.config_error = Some(e.to_string());
+                client
+            }
+        }
+    }
 
+    pub fn new_with_proxy(api_key: Option<String>, proxy_url: Option<&str>) -> Result<Self> {
+        let mut builder = LensClientBuilder::new();
+        if let Some(key) = api_key {
+            builder = builder.api_key(key);
+        }
         if let Some(proxy) = proxy_url {
-            let proxy_obj = reqwest::Proxy::all(proxy)
-                .map_err(|e| anyhow!("Failed to create proxy from URL '{}': {}", proxy, e))?;
-            client_builder = client_builder.proxy(proxy_obj);
+            builder = builder.proxy(proxy);
         }
 
-        let client = client_builder
-            .build()
-            .map_err(|e| anyhow!("Failed to build reqwest client: {}", e))?;
+        builder.build()
+    }
 
-        Ok(Self {
-            client,
-            api_key: api_key.unwrap_or_else(|| DEFAULT_API_KEY.to_string()),
-        })
+    /// Returns a builder for configuring endpoint, locale, headers and timeouts.
+    pub fn builder() -> LensClientBuilder {
+        LensClientBuilder::new()
     }
 
-    pub async fn process_image_path(
-        &self,
-        path: &str,
-        lang: Option<&str>,
-    ) -> anyhow::Result<LensResult> {
-        let processed = image_processor::process_image_from_path(path)?;
-        self.send_request(processed, lang).await
+    pub async fn process_image_path(&self, path: &str, lang: Option<&str>) -> Result<LensResult> {
+        let data = tokio::fs::read(path).await?;
+        self.process_encoded(data, lang).await
     }
 
     pub async fn process_image_bytes(
         &self,
         bytes: &[u8],
         lang: Option<&str>,
-    ) -> anyhow::Result<LensResult> {
-        let processed = image_processor::process_image_from_bytes(bytes)?;
+    ) -> Result<LensResult> {
+        self.process_encoded(bytes.to_vec(), lang).await
+    }
+
+    /// OCRs an already decoded image, skipping the encode/decode round trip.
+    pub async fn process_image(&self, img: DynamicImage, lang: Option<&str>) -> Result<LensResult> {
+        self.process_decoded(img, None, lang).await
+    }
+
+    /// OCRs an uncompressed RGBA or BGRA buffer, e.g. a screen capture.
+    pub async fn process_image_raw(
+        &self,
+        raw: &RawImage<'_>,
+        lang: Option<&str>,
+    ) -> Result<LensResult> {
+        self.process_decoded(raw.to_image()?, None, lang).await
+    }
+
+    async fn process_encoded(&self, bytes: Vec<u8>, lang: Option<&str>) -> Result<LensResult> {
+        let bytes: Arc<[u8]> = bytes.into();
+        let data = Arc::clone(&bytes);
+        let img = run_blocking(move || image_processor::load_image_from_bytes(&data)).await?;
+        self.process_decoded(img, Some(bytes), lang).await
+    }
+
+    async fn process_decoded(
+        &self,
+        img: DynamicImage,
+        original_bytes: Option<Arc<[u8]>>,
+        lang: Option<&str>,
+    ) -> Result<LensResult> {
+        let rotation = self.rotation;
+        let img = rotation.apply(img);
+        let original_bytes = original_bytes.filter(|_| rotation == Rotation::None);
+
+        let (width, height) = (img.width(), img.height());
+        let deskew_source = self.auto_deskew.then(|| img.clone());
+        let mut result = self.process_upright(img, original_bytes, lang).await?;
+
+        if let Some(source) = deskew_source
+            && let Some(angle) = orientation::dominant_rotation(&result)
+            && angle.abs().to_degrees() >= DEFAULT_DESKEW_MIN_ANGLE_DEG
+        {
+            let deskewed = run_blocking(move || Ok(orientation::deskew(&source, angle))).await?;
+            // The first pass is still a usable result, so a failed retry only loses the
+            // improvement.
+            match self.process_upright(deskewed, None, lang).await {
+                Ok(second_pass) => {
+                    result = orientation::undo_deskew(second_pass, angle, width, height)
+                }
+                Err(e) => log::warn!("Deskewed pass failed, keeping the skewed result: {}", e),
+            }
+        }
+
+        let mut result = rotation.unapply(result);
+        result.reorder(self.reading_order);
+        Ok(result)
+    }
+
+    async fn process_upright(
+        &self,
+        img: DynamicImage,
+        original_bytes: Option<Arc<[u8]>>,
+        lang: Option<&str>,
+    ) -> Result<LensResult> {
+        if let Some(tiling) = &self.tiling
+            && tiling.needs_tiling(img.width(), img.height())
+        {
+            return self.process_image_tiled(img, lang, tiling).await;
+        }
+
+        let options = self.image_options.clone();
+        let processed = run_blocking(move || {
+            image_processor::process_decoded_image(img, original_bytes.as_deref(), &options)
+        })
+        .await?;
         self.send_request(processed, lang).await
     }
 
@@ -113,7 +323,27 @@ impl LensClient {
         &self,
         image: image_processor::ProcessedImage,
         lang: Option<&str>,
-    ) -> anyhow::Result<LensResult> {
+    ) -> Result<LensResult> {
+        if let Some(message) = &self.config_error {
+            return Err(LensError::Config(message.clone()));
+        }
+
+        let lang = lang.unwrap_or("en");
+        let dimensions = image.dimensions();
+
+        let cache_key = self
+            .cache
+            .as_ref()
+            .map(|_| CacheKey::new(&image.bytes, lang));
+        if let (Some(cache), Some(key)) = (&self.cache, &cache_key)
+            && let Some(cached) = cache.get(key)
+        {
+            match LensOverlayServerResponse::decode(cached) {
+                Ok(server_response) => return self.parse_response(server_response, dimensions),
+                Err(e) => log::warn!("Ignoring undecodable cache entry {}: {}", key.as_str(), e),
+            }
+        }
+
         let request_id_val = rand::random::<u64>();
 
         let req_proto = LensOverlayServerRequest {
@@ -128,9 +358,9 @@ impl LensClient {
                         platform: Platform::Web as i32,
                         surface: Surface::Chromium as i32,
                         locale_context: Some(LocaleContext {
-                            language: lang.unwrap_or("en").to_string(),
-                            region: DEFAULT_CLIENT_REGION.to_string(),
-                            time_zone: DEFAULT_CLIENT_TIME_ZONE.to_string(),
+                            language: lang.to_string(),
+                            region: self.region.clone(),
+                            time_zone: self.time_zone.clone(),
                         }),
                     }),
                 }),
@@ -148,55 +378,109 @@ impl LensClient {
 
         let mut payload_bytes = Vec::new();
         req_proto.encode(&mut payload_bytes)?;
+        let payload = bytes::Bytes::from(payload_bytes);
+
+        let mut attempt = 1;
+        loop {
+            match self.send_once(payload.clone()).await {
+                Ok(server_response) => {
+                    if let (Some(cache), Some(key)) = (&self.cache, &cache_key) {
+                        cache.put(key, server_response.encode_to_vec().into());
+                    }
+                    return self.parse_response(server_response, dimensions);
+                }
+                Err(e)
+                    if attempt < self.retry_policy.max_attempts
+                        && self.retry_policy.is_retryable(&e) =>
+                {
+                    let delay = self.retry_policy.delay_for(attempt, &e);
+                    log::warn!(
+                        "Lens request attempt {} failed ({}), retrying in {:?}",
+                        attempt,
+                        e,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            CONTENT_TYPE,
-            HeaderValue::from_static("application/x-protobuf"),
-        );
-        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
-        headers.insert("X-Goog-Api-Key", HeaderValue::from_str(&self.api_key)?);
+    async fn send_once(&self, payload: bytes::Bytes) -> Result<LensOverlayServerResponse> {
+        let _permit = match &self.in_flight {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("in-flight semaphore is never closed"),
+            ),
+            None => None,
+        };
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
 
         let response = self
             .client
-            .post(LENS_CRUPLOAD_ENDPOINT)
-            .headers(headers)
-            .body(payload_bytes)
+            .post(&self.endpoint)
+            .headers(self.headers.clone())
+            .body(payload)
             .send()
             .await?;
 
         if !response.status().is_success() {
             let status = response.status();
-            let text = response.text().await?;
-            return Err(anyhow!("API Error {}: {}", status, text));
+            // Only the delta-seconds form; an HTTP-date is ignored and backoff applies.
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let body = response.text().await?;
+
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                return Err(LensError::RateLimited { retry_after, body });
+            }
+            return Err(LensError::Http {
+                status,
+                body,
+                retry_after,
+            });
         }
 
         let resp_bytes = response.bytes().await?;
 
-        let server_response = LensOverlayServerResponse::decode(resp_bytes)
-            .map_err(|e| anyhow!("Failed to decode protobuf response: {}", e))?;
-
-        self.parse_response(server_response)
+        Ok(LensOverlayServerResponse::decode(resp_bytes)?)
     }
 
     // --- Parsing Logic (Ported from api.py) ---
 
-    fn parse_response(&self, response: LensOverlayServerResponse) -> anyhow::Result<LensResult> {
+    fn parse_response(
+        &self,
+        response: LensOverlayServerResponse,
+        dimensions: ImageDimensions,
+    ) -> Result<LensResult> {
         let mut paragraphs_list = Vec::new();
         let mut full_text_buffer = String::new();
+        let mut language = None;
 
         // Extract OCR Data
-        if let Some(objects_res) = &response.objects_response {
-            if let Some(text_struct) = &objects_res.text {
-                if let Some(layout) = &text_struct.text_layout {
-                    for p in &layout.paragraphs {
-                        let parsed_para = self.parse_paragraph(p);
+        if let Some(objects_res) = &response.objects_response
+            && let Some(text_struct) = &objects_res.text
+        {
+            language = non_empty(&text_struct.content_language);
 
-                        full_text_buffer.push_str(&parsed_para.text);
-                        full_text_buffer.push('\n'); // Standardize paragraph separation
+            if let Some(layout) = &text_struct.text_layout {
+                for p in &layout.paragraphs {
+                    let parsed_para = self.parse_paragraph(p);
 
-                        paragraphs_list.push(parsed_para);
-                    }
+                    full_text_buffer.push_str(&parsed_para.text);
+                    full_text_buffer.push('\n'); // Standardize paragraph separation
+
+                    paragraphs_list.push(parsed_para);
                 }
             }
         }
@@ -204,10 +488,16 @@ impl LensClient {
         // Extract Translation
         let translation = self.extract_translation(&response);
 
+        if paragraphs_list.is_empty() && translation.is_none() {
+            return Err(LensError::EmptyResult);
+        }
+
         Ok(LensResult {
             full_text: full_text_buffer.trim().to_string(),
             paragraphs: paragraphs_list,
             translation,
+            language,
+            image: dimensions,
         })
     }
 
@@ -228,6 +518,7 @@ impl LensClient {
             text: full_para_text,
             lines: lines_list,
             geometry,
+            language: non_empty(&p.content_language),
         }
     }
 
@@ -281,14 +572,12 @@ impl LensClient {
 
         if let Some(objects_res) = &response.objects_response {
             for gleam in &objects_res.deep_gleams {
-                if let Some(trans_data) = &gleam.translation {
-                    if let Some(status) = &trans_data.status {
-                        if status.code == TranslationStatus::Success as i32 {
-                            if !trans_data.translation.is_empty() {
-                                translations.push(trans_data.translation.clone());
-                            }
-                        }
-                    }
+                if let Some(trans_data) = &gleam.translation
+                    && let Some(status) = &trans_data.status
+                    && status.code == TranslationStatus::Success as i32
+                    && !trans_data.translation.is_empty()
+                {
+                    translations.push(trans_data.translation.clone());
                 }
             }
         }
@@ -300,3 +589,364 @@ impl LensClient {
         }
     }
 }
+
+/// Runs decoding, resizing and encoding on the blocking thread pool so they do not stall other
+/// requests on the same runtime.
+pub(crate) async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+// --- Client Builder ---
+
+/// Configures a [`LensClient`].
+///
+/// Every setting defaults to the values in [`constants`], so `LensClientBuilder::new().build()`
+/// produces the same client as [`LensClient::new`].
+#[derive(Debug, Clone)]
+pub struct LensClientBuilder {
+    api_key: String,
+    endpoint: String,
+    user_agent: String,
+    region: String,
+    time_zone: String,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    proxy_url: Option<String>,
+    extra_headers: HeaderMap,
+    client: Option<reqwest::Client>,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    max_in_flight: Option<usize>,
+    cache: Option<Arc<dyn ResultCache>>,
+    image_options: ImageProcessingOptions,
+    tiling: Option<TilingOptions>,
+    rotation: Rotation,
+    auto_deskew: bool,
+    max_download_bytes: u64,
+    reading_order: ReadingOrder,
+}
+
+impl Default for LensClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LensClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            api_key: DEFAULT_API_KEY.to_string(),
+            endpoint: LENS_CRUPLOAD_ENDPOINT.to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            region: DEFAULT_CLIENT_REGION.to_string(),
+            time_zone: DEFAULT_CLIENT_TIME_ZONE.to_string(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            connect_timeout: None,
+            proxy_url: None,
+            extra_headers: HeaderMap::new(),
+            client: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+            max_in_flight: None,
+            cache: None,
+            image_options: ImageProcessingOptions::default(),
+            tiling: None,
+            rotation: Rotation::None,
+            auto_deskew: false,
+            max_download_bytes: DEFAULT_MAX_DOWNLOAD_BYTES,
+            reading_order: ReadingOrder::Server,
+        }
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = api_key.into();
+        self
+    }
+
+    /// Overrides the upload URL, e.g. to point at a local mock server.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Region reported in the request's locale context (e.g. `"JP"`).
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// IANA time zone reported in the request's locale context (e.g. `"Asia/Tokyo"`).
+    pub fn time_zone(mut self, time_zone: impl Into<String>) -> Self {
+        self.time_zone = time_zone.into();
+        self
+    }
+
+    /// Total request timeout. Ignored when a pre-built client is supplied.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connection timeout. Ignored when a pre-built client is supplied.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Routes all traffic through the given proxy. Ignored when a pre-built client is supplied.
+    pub fn proxy(mut self, proxy_url: impl Into<String>) -> Self {
+        self.proxy_url = Some(proxy_url.into());
+        self
+    }
+
+    /// Adds a header sent with every request. Overrides the defaults if the name collides.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.extra_headers.insert(name, value);
+        self
+    }
+
+    /// Adds several headers sent with every request.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.extra_headers.extend(headers);
+        self
+    }
+
+    /// Uses an existing `reqwest::Client` instead of building one.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets how transient failures are retried. Use [`RetryPolicy::none`] to disable retries.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Limits how often requests are sent, across all clones of the built client.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Limits how many requests are in flight at once, across all clones of the built client.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Serves repeated requests for the same image and language from `cache`. The cache is
+    /// shared by all clones of the built client.
+    pub fn cache(mut self, cache: impl ResultCache + 'static) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Like [`cache`](Self::cache), for a cache that is already shared elsewhere.
+    pub fn shared_cache(mut self, cache: Arc<dyn ResultCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Sets how images are resized before upload.
+    pub fn image_options(mut self, options: ImageProcessingOptions) -> Self {
+        self.image_options = options;
+        self
+    }
+
+    /// Splits images larger than the tile size into overlapping tiles instead of downscaling
+    /// them. See [`LensClient::process_image_tiled`]; [`build`](Self::build) fails when the
+    /// options do not [`validate`](TilingOptions::validate).
+    pub fn tiling(mut self, options: TilingOptions) -> Self {
+        self.tiling = Some(options);
+        self
+    }
+
+    /// Rotates images clockwise before upload. Geometry is still reported relative to the image
+    /// as passed in.
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// When the first pass finds text tilted by more than half a degree, straightens the image
+    /// and OCRs it again. Geometry is reported relative to the unstraightened image.
+    pub fn auto_deskew(mut self, enabled: bool) -> Self {
+        self.auto_deskew = enabled;
+        self
+    }
+
+    /// Largest image [`LensClient::process_image_url`] will download.
+    pub fn max_download_bytes(mut self, max: u64) -> Self {
+        self.max_download_bytes = max;
+        self
+    }
+
+    /// Orders paragraphs, lines and `full_text` by their position on the page.
+    pub fn reading_order(mut self, order: ReadingOrder) -> Self {
+        self.reading_order = order;
+        self
+    }
+
+    pub fn build(self) -> Result<LensClient> {
+        if let Some(tiling) = &self.tiling {
+            tiling.validate()?;
+        }
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let mut client_builder = reqwest::Client::builder().timeout(self.timeout);
+
+                if let Some(connect_timeout) = self.connect_timeout {
+                    client_builder = client_builder.connect_timeout(connect_timeout);
+                }
+
+                if let Some(proxy) = &self.proxy_url {
+                    let proxy_obj = reqwest::Proxy::all(proxy).map_err(|e| {
+                        LensError::Config(format!(
+                            "Failed to create proxy from URL '{}': {}",
+                            proxy, e
+                        ))
+                    })?;
+                    client_builder = client_builder.proxy(proxy_obj);
+                }
+
+                client_builder.build().map_err(|e| {
+                    LensError::Config(format!("Failed to build reqwest client: {}", e))
+                })?
+            }
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-protobuf"),
+        );
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(&self.user_agent).map_err(|e| {
+                LensError::Config(format!("Invalid user agent '{}': {}", self.user_agent, e))
+            })?,
+        );
+        headers.insert(
+            "X-Goog-Api-Key",
+            HeaderValue::from_str(&self.api_key)
+                .map_err(|e| LensError::Config(format!("Invalid API key: {}", e)))?,
+        );
+        headers.extend(self.extra_headers);
+
+        Ok(LensClient {
+            client,
+            endpoint: self.endpoint,
+            headers,
+            region: self.region,
+            time_zone: self.time_zone,
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limit.map(|l| Arc::new(RateLimiter::new(l))),
+            in_flight: self
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            cache: self.cache,
+            image_options: self.image_options,
+            tiling: self.tiling,
+            rotation: self.rotation,
+            auto_deskew: self.auto_deskew,
+            max_download_bytes: self.max_download_bytes,
+            reading_order: self.reading_order,
+            config_error: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+    use crate::test_support::{line, paragraph, result, word};
+
+    #[tokio::test]
+    async fn new_reports_an_invalid_api_key_on_request() {
+        let client = LensClient::new(Some("bad\nkey".to_string()));
+        let img = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+
+        let err = client.process_image(img, None).await.unwrap_err();
+        assert!(matches!(err, LensError::Config(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn builder_rejects_an_invalid_api_key() {
+        let result = LensClient::builder().api_key("bad\nkey").build();
+        assert!(matches!(result, Err(LensError::Config(_))));
+    }
+
+    /// A result with one single-line paragraph per entry of `texts`, translated as `translation`.
+    fn translated(texts: &[&str], translation: Option<&str>) -> LensResult {
+        let paragraphs = texts
+            .iter()
+            .map(|t| paragraph(vec![line(vec![word(t, "", None)], None)], None, None))
+            .collect();
+        let mut result = result(paragraphs, 100, 100);
+        result.translation = translation.map(str::to_string);
+        result
+    }
+
+    #[test]
+    fn translation_lines_map_one_to_one_onto_paragraphs() {
+        let result = translated(&["a", "b"], Some("first\n\n  second  \n"));
+        assert_eq!(result.paragraph_translations(), ["first", "second"]);
+    }
+
+    #[test]
+    fn translation_lines_are_assigned_by_their_share_of_the_text() {
+        // Each paragraph is half the source, so the first half of the translation goes to the
+        // first paragraph.
+        let result = translated(&["aaaaaaaaaa", "bbbbbbbbbb"], Some("aaaa\nbbbb\ncccccccc"));
+        assert_eq!(result.paragraph_translations(), ["aaaa bbbb", "cccccccc"]);
+
+        let result = translated(&["a", "b", "c"], Some("only"));
+        assert_eq!(result.paragraph_translations(), ["", "only", ""]);
+    }
+
+    #[test]
+    fn translations_without_paragraphs_keep_every_line() {
+        let result = translated(&[], Some("a\n\nb"));
+        assert_eq!(result.paragraph_translations(), ["a", "b"]);
+
+        let result = translated(&["a", "b"], None);
+        assert_eq!(result.paragraph_translations(), ["", ""]);
+        assert!(translated(&[], None).paragraph_translations().is_empty());
+    }
+
+    #[test]
+    fn builder_rejects_invalid_tiling() {
+        let tiling = TilingOptions {
+            overlap: 1500,
+            tile_size: 1500,
+            ..TilingOptions::default()
+        };
+        let result = LensClient::builder().tiling(tiling).build();
+        assert!(matches!(result, Err(LensError::Config(_))));
+    }
+}
@@ -1,61 +1,407 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use arboard::Clipboard;
-use clap::Parser;
-use chrome_lens_ocr::LensClient;
+use chrome_lens_ocr::{
+    BatchOptions, ImageDimensions, ImageProcessingOptions, ImageSource, LensClient, LensError,
+    LensResult, PreprocessStep, document,
+    export::{
+        alto, hocr, page_xml,
+        pdf::{self, PdfOptions, PdfPage},
+    },
+    image_processor,
+    render::{
+        self,
+        overlay::OverlayOptions,
+        translation::{self, TranslationRenderOptions},
+    },
+};
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum, error::ErrorKind};
+use futures::StreamExt;
+use image::DynamicImage;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Path to the image file
+    /// Path to the image file, or an http(s) URL to download it from
     image_path: String,
 
     /// Output the text to a file with the same name as the input image
     #[arg(long)]
     text: bool,
 
-    /// Copy the text to the clipboard
+    /// Copy the text to the clipboard; not available with --format pdf
     #[arg(long)]
     clip: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Draw the detected paragraph, line and word boxes onto the image and save it (PNG or SVG);
+    /// raster output is only labeled with --font. With --format pdf, the first page is drawn
+    #[arg(long, value_name = "PATH")]
+    debug_overlay: Option<PathBuf>,
+
+    /// Draw the translation over each paragraph of the image and save it; requires --font. With
+    /// --format pdf, the first page is drawn
+    #[arg(long, value_name = "PATH", requires = "font")]
+    translate_overlay: Option<PathBuf>,
+
+    /// TrueType/OpenType font for overlay labels and translated text
+    #[arg(long, value_name = "PATH")]
+    font: Option<PathBuf>,
+
+    /// Comma-separated preprocessing steps applied in order before upload
+    #[arg(long, value_enum, value_delimiter = ',')]
+    preprocess: Vec<Preprocess>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// Plain text
+    Text,
+    /// hOCR (HTML with word bounding boxes)
+    Hocr,
+    /// ALTO v4 XML
+    Alto,
+    /// PRImA PAGE XML
+    Page,
+    /// Searchable PDF, written next to the input as NAME.ocr.pdf; every page of a PDF, TIFF or
+    /// GIF is included
+    Pdf,
+}
+
+impl Args {
+    fn wants_overlays(&self) -> bool {
+        self.debug_overlay.is_some() || self.translate_overlay.is_some()
+    }
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Hocr => "hocr",
+            Format::Alto => "alto.xml",
+            Format::Page => "page.xml",
+            // Distinct from the input's own extension so a PDF scan is never overwritten.
+            Format::Pdf => "ocr.pdf",
+        }
+    }
+
+    fn render(self, result: &LensResult, image_path: &str) -> String {
+        match self {
+            Format::Text => result.full_text.clone(),
+            Format::Hocr => hocr::to_hocr(result, Some(image_path)),
+            Format::Alto => alto::to_alto(result, Some(image_path)),
+            Format::Page => page_xml::to_page_xml(result, Some(image_path)),
+            Format::Pdf => unreachable!("PDF output is written by write_searchable_pdf"),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Preprocess {
+    Grayscale,
+    AutoContrast,
+    Equalize,
+    Otsu,
+    Adaptive,
+    Denoise,
+    Sharpen,
+    Invert,
+}
+
+impl Preprocess {
+    fn step(self) -> PreprocessStep {
+        match self {
+            Preprocess::Grayscale => PreprocessStep::Grayscale,
+            Preprocess::AutoContrast => PreprocessStep::AutoContrast,
+            Preprocess::Equalize => PreprocessStep::EqualizeHistogram,
+            Preprocess::Otsu => PreprocessStep::OtsuThreshold,
+            Preprocess::Adaptive => PreprocessStep::AdaptiveThreshold { block_radius: 15 },
+            Preprocess::Denoise => PreprocessStep::MedianDenoise { radius: 1 },
+            Preprocess::Sharpen => PreprocessStep::Sharpen {
+                sigma: 1.0,
+                threshold: 0,
+            },
+            Preprocess::Invert => PreprocessStep::Invert,
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     env_logger::init();
 
-    let args = Args::parse();
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut command = Args::command();
+    let args = Args::from_arg_matches(&command.get_matches_mut())
+        .unwrap_or_else(|e| e.format(&mut command).exit());
     let image_path = &args.image_path;
+    if args.clip && matches!(args.format, Format::Pdf) {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--clip cannot be used with --format pdf",
+            )
+            .exit();
+    }
 
-    let client = LensClient::new(None);
+    let image_options = ImageProcessingOptions {
+        preprocess: args.preprocess.iter().map(|p| p.step()).collect(),
+        ..Default::default()
+    };
+    let client = LensClient::builder().image_options(image_options).build()?;
 
-    match client.process_image_path(image_path, Some("en")).await {
-        Ok(result) => {
-            if !args.text && !args.clip {
-                println!("{}", result.full_text);
-            }
+    if let Format::Pdf = args.format {
+        return write_searchable_pdf(&client, &args).await;
+    }
 
-            if args.text {
-                let mut path = PathBuf::from(image_path);
-                path.set_extension("txt");
-                fs::write(&path, &result.full_text)?;
-                // println!("Text saved to: {:?}", path);
-            }
+    let data = read_input(&client, image_path).await?;
+    // Decoded like the upload, with EXIF orientation applied, and at most once.
+    let mut decoded = None;
+    let result = match client.process_image_bytes(&data, Some("en")).await {
+        Err(LensError::EmptyResult) => {
+            let img = image_processor::load_image_from_bytes(&data)?;
+            let result = empty_result(img.width(), img.height());
+            decoded = Some(img);
+            result
+        }
+        result => result?,
+    };
 
-            if args.clip {
-                match Clipboard::new() {
-                    Ok(mut clipboard) => {
-                        if let Err(e) = clipboard.set_text(&result.full_text) {
-                            eprintln!("Failed to copy to clipboard: {}", e);
-                        }
-                    }
-                    Err(e) => eprintln!("Failed to initialize clipboard: {}", e),
+    if args.wants_overlays() {
+        let img = match decoded {
+            Some(img) => img,
+            None => image_processor::load_image_from_bytes(&data)?,
+        };
+        write_overlays(&args, &img, &result)?;
+    }
+
+    let output = args.format.render(&result, image_path);
+
+    if !args.text && !args.clip {
+        println!("{}", output);
+    }
+
+    if args.text {
+        let path = output_path(image_path, args.format.extension());
+        fs::write(&path, &output)?;
+        // println!("Text saved to: {:?}", path);
+    }
+
+    if args.clip {
+        match Clipboard::new() {
+            Ok(mut clipboard) => {
+                if let Err(e) = clipboard.set_text(&output) {
+                    eprintln!("Failed to copy to clipboard: {}", e);
                 }
             }
+            Err(e) => eprintln!("Failed to initialize clipboard: {}", e),
         }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
+    }
+
+    Ok(())
+}
+
+/// The result for a `width` x `height` page with no text, so blank pages produce empty output
+/// instead of an error.
+fn empty_result(width: u32, height: u32) -> LensResult {
+    LensResult {
+        full_text: String::new(),
+        paragraphs: Vec::new(),
+        translation: None,
+        language: None,
+        image: ImageDimensions {
+            original_width: width,
+            original_height: height,
+            sent_width: width,
+            sent_height: height,
+            scale_factor: 1.0,
+        },
+    }
+}
+
+/// Saves the debug and translation overlays requested on the command line.
+fn write_overlays(
+    args: &Args,
+    img: &DynamicImage,
+    result: &LensResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let font = args.font.as_ref().map(render::load_font).transpose()?;
+
+    if let Some(path) = &args.debug_overlay {
+        let is_svg = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+        if font.is_none() && !is_svg {
+            eprintln!(
+                "No --font given; {} shows boxes without labels",
+                path.display()
+            );
+        }
+        let options = OverlayOptions {
+            labels: true,
+            font: font.clone(),
+            ..Default::default()
+        };
+        render::overlay::save_overlay(img, result, path, &options)?;
+    }
+
+    if let Some(path) = &args.translate_overlay
+        && let Some(font) = font
+    {
+        if result.translation.is_none() {
+            eprintln!("No translation returned; the image is saved unchanged");
+        }
+        let options = TranslationRenderOptions::new(font);
+        translation::render_translation(img, result, &options).save(path)?;
+    }
+    Ok(())
+}
+
+/// OCRs every page of `input` and writes them as one searchable PDF. Pages that fail OCR keep
+/// their image without a text layer; pages that cannot be decoded are left out. Overlays are
+/// drawn for the first page. Fails without writing when OCR failed on every page.
+async fn write_searchable_pdf(
+    client: &LensClient,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let input = args.image_path.as_str();
+    let output = output_path(input, Format::Pdf.extension());
+    if !is_url(input) && same_file(input, &output) {
+        return Err(format!("refusing to overwrite the input file {:?}", output).into());
+    }
+    let data = read_input(client, input).await?;
+
+    let mut pages = Vec::new();
+    for (index, page) in document::load_pages(&data)?.into_iter().enumerate() {
+        match page {
+            Ok(image) => pages.push((index, image)),
+            Err(e) => eprintln!("Page {}: {}", index + 1, e),
+        }
+    }
+
+    let results: Vec<_> = client
+        .process_batch(
+            pages
+                .iter()
+                .map(|(_, image)| ImageSource::Image(image.clone())),
+            Some("en"),
+            BatchOptions::default(),
+        )
+        .collect()
+        .await;
+
+    let mut pdf_pages = Vec::with_capacity(pages.len());
+    for ((index, image), (_, result)) in pages.iter().zip(&results) {
+        // Blank pages are kept without a text layer and are not worth a warning.
+        if let Err(e) = result
+            && !matches!(e, LensError::EmptyResult)
+        {
+            eprintln!("Page {}: {}", index + 1, e);
         }
+        pdf_pages.push(PdfPage {
+            image,
+            result: result.as_ref().ok(),
+        });
     }
 
+    if args.wants_overlays()
+        && let Some(((index, image), (_, result))) = pages.first().zip(results.first())
+    {
+        match result {
+            Ok(result) => write_overlays(args, image, result)?,
+            Err(LensError::EmptyResult) => {
+                write_overlays(args, image, &empty_result(image.width(), image.height()))?
+            }
+            Err(_) => eprintln!("Page {} has no OCR result; overlays are skipped", index + 1),
+        }
+    }
+
+    // Blank pages count as recognized; only real failures on every page are an error.
+    let recognized = results
+        .iter()
+        .filter(|(_, r)| matches!(r, Ok(_) | Err(LensError::EmptyResult)))
+        .count();
+    if recognized == 0 {
+        return Err(format!("no page of {} could be recognized", input).into());
+    }
+
+    let pdf = pdf::to_searchable_pdf(&pdf_pages, &PdfOptions::default())?;
+    fs::write(&output, pdf)?;
     Ok(())
 }
+
+/// Whether `input` and `output` name the same existing file.
+fn same_file(input: &str, output: &Path) -> bool {
+    match (fs::canonicalize(input), fs::canonicalize(output)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Downloads `input` if it is a URL, otherwise reads it from disk.
+async fn read_input(client: &LensClient, input: &str) -> chrome_lens_ocr::Result<Vec<u8>> {
+    if is_url(input) {
+        client.download_image(input).await
+    } else {
+        Ok(fs::read(input)?)
+    }
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// `input` with its extension replaced. For URLs, the last path segment is used as the file name
+/// in the current directory.
+fn output_path(input: &str, extension: &str) -> PathBuf {
+    let mut path = if is_url(input) {
+        let name = url::Url::parse(input)
+            .ok()
+            .and_then(|u| {
+                u.path_segments()
+                    .and_then(|mut segments| segments.next_back().map(str::to_string))
+            })
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "image".to_string());
+        PathBuf::from(name)
+    } else {
+        PathBuf::from(input)
+    };
+    path.set_extension(extension);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_output_never_replaces_a_pdf_input() {
+        let extension = Format::Pdf.extension();
+        assert_eq!(
+            output_path("scans/x.pdf", extension),
+            PathBuf::from("scans/x.ocr.pdf")
+        );
+        assert_eq!(
+            output_path("x.ocr.pdf", extension),
+            PathBuf::from("x.ocr.ocr.pdf")
+        );
+        assert_eq!(
+            output_path("https://example.com/a/page.tiff", extension),
+            PathBuf::from("page.ocr.pdf")
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Manual rotation and automatic deskewing, with results mapped back to the caller's orientation.
+
+use std::f32::consts::PI;
+
+use image::{DynamicImage, Rgba, RgbaImage, imageops};
+use imageproc::geometric_transformations::{Interpolation, rotate_about_center};
+
+use crate::{GeometryData, LensResult, image_processor::ImageDimensions};
+
+/// A clockwise rotation applied to the image before upload.
+///
+/// Results are rotated back, so geometry always lines up with the image the caller passed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    pub fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::None => img,
+            Rotation::Rotate90 => img.rotate90(),
+            Rotation::Rotate180 => img.rotate180(),
+            Rotation::Rotate270 => img.rotate270(),
+        }
+    }
+
+    /// Maps a result for the rotated image back onto the unrotated one.
+    pub fn unapply(self, mut result: LensResult) -> LensResult {
+        if self == Rotation::None {
+            return result;
+        }
+
+        result.map_geometry(|g| self.unapply_geometry(g));
+        if matches!(self, Rotation::Rotate90 | Rotation::Rotate270) {
+            let dims = &mut result.image;
+            std::mem::swap(&mut dims.original_width, &mut dims.original_height);
+            std::mem::swap(&mut dims.sent_width, &mut dims.sent_height);
+        }
+        result
+    }
+
+    fn unapply_geometry(self, g: &GeometryData) -> GeometryData {
+        // A w x h box at angle a is the same rectangle as an h x w box at angle a + 90 degrees,
+        // so quarter turns only swap the box's sides and leave the angle alone.
+        let (center_x, center_y, width, height) = match self {
+            Rotation::None => (g.center_x, g.center_y, g.width, g.height),
+            Rotation::Rotate90 => (g.center_y, 1.0 - g.center_x, g.height, g.width),
+            Rotation::Rotate180 => (1.0 - g.center_x, 1.0 - g.center_y, g.width, g.height),
+            Rotation::Rotate270 => (1.0 - g.center_y, g.center_x, g.height, g.width),
+        };
+
+        GeometryData {
+            center_x,
+            center_y,
+            width,
+            height,
+            rotation_z: g.rotation_z,
+            angle_deg: g.angle_deg,
+        }
+    }
+}
+
+/// The median `rotation_z` of all lines in `result`, in radians.
+pub fn dominant_rotation(result: &LensResult) -> Option<f32> {
+    let mut angles: Vec<f32> = result
+        .paragraphs
+        .iter()
+        .flat_map(|p| &p.lines)
+        .filter_map(|l| l.geometry.as_ref().map(|g| g.rotation_z))
+        .collect();
+    if angles.is_empty() {
+        return None;
+    }
+
+    angles.sort_by(f32::total_cmp);
+    Some(angles[angles.len() / 2])
+}
+
+/// Rotates `img` by `-angle` radians about its center so text at `angle` becomes level. The
+/// image is first centered on a white canvas that holds all of it once rotated, so no corner is
+/// cropped and the result is larger than `img`.
+pub fn deskew(img: &DynamicImage, angle: f32) -> DynamicImage {
+    let canvas = deskew_canvas(img.width(), img.height(), angle);
+    let mut expanded = RgbaImage::from_pixel(canvas.width, canvas.height, WHITE);
+    imageops::replace(
+        &mut expanded,
+        &img.to_rgba8(),
+        i64::from(canvas.offset_x),
+        i64::from(canvas.offset_y),
+    );
+
+    let rotated = rotate_about_center(&expanded, -angle, Interpolation::Bilinear, WHITE);
+    DynamicImage::ImageRgba8(rotated)
+}
+
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// The canvas a `width` x `height` image is deskewed on, and where the image sits on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DeskewCanvas {
+    width: u32,
+    height: u32,
+    offset_x: u32,
+    offset_y: u32,
+}
+
+/// The bounding box of a `width` x `height` image rotated by `angle` radians, with the image
+/// centered on it.
+fn deskew_canvas(width: u32, height: u32, angle: f32) -> DeskewCanvas {
+    let (sin, cos) = angle.sin_cos();
+    let (w, h) = (width as f32, height as f32);
+    // Rounding error in sin and cos must not grow the canvas by a pixel.
+    let fit = |size: f32, min: u32| ((size - 1e-3).ceil() as u32).max(min);
+    let canvas_width = fit(w * cos.abs() + h * sin.abs(), width);
+    let canvas_height = fit(w * sin.abs() + h * cos.abs(), height);
+
+    DeskewCanvas {
+        width: canvas_width,
+        height: canvas_height,
+        offset_x: (canvas_width - width) / 2,
+        offset_y: (canvas_height - height) / 2,
+    }
+}
+
+/// Maps a result for an image deskewed by `angle` back onto the original `width` x `height`
+/// image, removing the canvas that [`deskew`] added.
+pub fn undo_deskew(mut result: LensResult, angle: f32, width: u32, height: u32) -> LensResult {
+    let canvas = deskew_canvas(width, height, angle);
+    let (cw, ch) = (canvas.width as f32, canvas.height as f32);
+    let (w, h) = (width as f32, height as f32);
+    let (sin, cos) = angle.sin_cos();
+
+    result.map_geometry(|g| {
+        let (dx, dy) = ((g.center_x - 0.5) * cw, (g.center_y - 0.5) * ch);
+        // Rotated back about the canvas center, then moved from canvas to image pixels.
+        let x = cw / 2.0 + dx * cos - dy * sin - canvas.offset_x as f32;
+        let y = ch / 2.0 + dx * sin + dy * cos - canvas.offset_y as f32;
+        let rotation_z = g.rotation_z + angle;
+
+        GeometryData {
+            center_x: x / w,
+            center_y: y / h,
+            width: g.width * cw / w,
+            height: g.height * ch / h,
+            rotation_z,
+            angle_deg: rotation_z * (180.0 / PI),
+        }
+    });
+
+    let scale_factor = result.image.scale_factor;
+    result.image = ImageDimensions {
+        original_width: width,
+        original_height: height,
+        sent_width: (w * scale_factor).round() as u32,
+        sent_height: (h * scale_factor).round() as u32,
+        scale_factor,
+    };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Point,
+        test_support::{geometry, line, paragraph, result, word},
+    };
+
+    /// A result for a `width` x `height` image holding one word box.
+    fn single_box(g: GeometryData, width: u32, height: u32) -> LensResult {
+        result(
+            vec![paragraph(
+                vec![line(vec![word("x", "", Some(g))], None)],
+                None,
+                None,
+            )],
+            width,
+            height,
+        )
+    }
+
+    fn word_corners(result: &LensResult) -> [Point; 4] {
+        let g = result.paragraphs[0].lines[0].words[0]
+            .geometry
+            .clone()
+            .unwrap();
+        g.corners(result.image.original_width, result.image.original_height)
+    }
+
+    /// Asserts both corner sets describe the same quadrilateral, in any order.
+    fn assert_same_corners(actual: [Point; 4], expected: [Point; 4]) {
+        for e in expected {
+            assert!(
+                actual
+                    .iter()
+                    .any(|a| (a.x - e.x).abs() < 0.01 && (a.y - e.y).abs() < 0.01),
+                "{:?} not in {:?}",
+                e,
+                actual
+            );
+        }
+    }
+
+    /// Where a point of the image rotated by `rotation` came from in the 400x200 original.
+    fn to_original(rotation: Rotation, p: Point) -> Point {
+        let (x, y) = match rotation {
+            Rotation::None => (p.x, p.y),
+            Rotation::Rotate90 => (p.y, 200.0 - p.x),
+            Rotation::Rotate180 => (400.0 - p.x, 200.0 - p.y),
+            Rotation::Rotate270 => (400.0 - p.y, p.x),
+        };
+        Point { x, y }
+    }
+
+    #[test]
+    fn unapply_maps_boxes_onto_the_unrotated_image() {
+        for rotation in [Rotation::Rotate90, Rotation::Rotate180, Rotation::Rotate270] {
+            let rotated = rotation.apply(DynamicImage::new_rgb8(400, 200));
+            let (rw, rh) = (rotated.width(), rotated.height());
+            let before = single_box(geometry(0.3, 0.2, 0.25, 0.1, 15.0), rw, rh);
+            let expected = word_corners(&before).map(|p| to_original(rotation, p));
+
+            let after = rotation.unapply(before);
+            assert_eq!(
+                (after.image.original_width, after.image.original_height),
+                (400, 200)
+            );
+            assert_same_corners(word_corners(&after), expected);
+        }
+    }
+
+    #[test]
+    fn undo_deskew_rotates_boxes_back_onto_the_original() {
+        let angle = 12f32.to_radians();
+        let canvas = deskew_canvas(400, 200, angle);
+        assert!(canvas.width > 400 && canvas.height > 200, "{:?}", canvas);
+
+        let deskewed = single_box(
+            geometry(0.7, 0.4, 0.2, 0.05, 0.0),
+            canvas.width,
+            canvas.height,
+        );
+        let (cx, cy) = (canvas.width as f32 / 2.0, canvas.height as f32 / 2.0);
+        let (sin, cos) = angle.sin_cos();
+        let expected = word_corners(&deskewed).map(|p| {
+            let (dx, dy) = (p.x - cx, p.y - cy);
+            Point {
+                x: cx + dx * cos - dy * sin - canvas.offset_x as f32,
+                y: cy + dx * sin + dy * cos - canvas.offset_y as f32,
+            }
+        });
+
+        let restored = undo_deskew(deskewed, angle, 400, 200);
+        assert_eq!(
+            (
+                restored.image.original_width,
+                restored.image.original_height
+            ),
+            (400, 200)
+        );
+        let g = restored.paragraphs[0].lines[0].words[0]
+            .geometry
+            .clone()
+            .unwrap();
+        assert!((g.angle_deg - 12.0).abs() < 1e-4);
+        assert_same_corners(word_corners(&restored), expected);
+    }
+
+    #[test]
+    fn deskew_keeps_the_corners() {
+        let angle = 12f32.to_radians();
+        // A black 4x4 block in each corner.
+        let img = RgbaImage::from_fn(400, 200, |x, y| {
+            if !(4..396).contains(&x) && !(4..196).contains(&y) {
+                Rgba([0, 0, 0, 255])
+            } else {
+                WHITE
+            }
+        });
+        let deskewed = deskew(&DynamicImage::ImageRgba8(img), angle).to_rgba8();
+        let canvas = deskew_canvas(400, 200, angle);
+        assert_eq!(deskewed.dimensions(), (canvas.width, canvas.height));
+
+        let (cx, cy) = (canvas.width as f32 / 2.0, canvas.height as f32 / 2.0);
+        let (sin, cos) = (-angle).sin_cos();
+        for (x, y) in [(2.0, 2.0), (398.0, 2.0), (398.0, 198.0), (2.0, 198.0)] {
+            let (dx, dy) = (
+                x + canvas.offset_x as f32 - cx,
+                y + canvas.offset_y as f32 - cy,
+            );
+            let (qx, qy) = (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos);
+            let pixel = deskewed.get_pixel(qx as u32, qy as u32);
+            assert!(pixel[0] < 255, "corner ({}, {}) was cropped", x, y);
+        }
+    }
+}
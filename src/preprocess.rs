@@ -0,0 +1,109 @@
+//! Optional image clean-up applied before upload to help with low-contrast scans and screentones.
+
+use image::{DynamicImage, GrayImage, Luma};
+use imageproc::{
+    contrast::{ThresholdType, adaptive_threshold, equalize_histogram, otsu_level, threshold},
+    filter::median_filter,
+};
+
+/// One preprocessing operation. Steps run in the order given in
+/// [`ImageProcessingOptions::preprocess`](crate::image_processor::ImageProcessingOptions).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreprocessStep {
+    Grayscale,
+    /// Linearly stretches luminance so the darkest pixel becomes black and the brightest white.
+    AutoContrast,
+    /// Histogram equalization. Converts to grayscale.
+    EqualizeHistogram,
+    /// Global binarization at the Otsu level. Converts to grayscale.
+    OtsuThreshold,
+    /// Binarization against the mean of a `(2 * block_radius + 1)` square window. Converts to
+    /// grayscale.
+    AdaptiveThreshold {
+        block_radius: u32,
+    },
+    /// Median filter with the given radius, for removing screentone dots and speckle.
+    MedianDenoise {
+        radius: u32,
+    },
+    /// Unsharp mask with Gaussian `sigma`; differences below `threshold` are left alone.
+    Sharpen {
+        sigma: f32,
+        threshold: i32,
+    },
+    /// Inverts colors, for light text on a dark background.
+    Invert,
+}
+
+/// Applies `steps` to `img` in order.
+pub fn apply(mut img: DynamicImage, steps: &[PreprocessStep]) -> DynamicImage {
+    for step in steps {
+        img = apply_step(img, *step);
+    }
+    img
+}
+
+fn apply_step(img: DynamicImage, step: PreprocessStep) -> DynamicImage {
+    match step {
+        PreprocessStep::Grayscale => DynamicImage::ImageLuma8(img.to_luma8()),
+        PreprocessStep::AutoContrast => auto_contrast(img),
+        PreprocessStep::EqualizeHistogram => {
+            DynamicImage::ImageLuma8(equalize_histogram(&img.to_luma8()))
+        }
+        PreprocessStep::OtsuThreshold => {
+            let gray = img.to_luma8();
+            let level = otsu_level(&gray);
+            DynamicImage::ImageLuma8(threshold(&gray, level, ThresholdType::Binary))
+        }
+        PreprocessStep::AdaptiveThreshold { block_radius } => {
+            DynamicImage::ImageLuma8(adaptive_threshold(&img.to_luma8(), block_radius.max(1)))
+        }
+        PreprocessStep::MedianDenoise { radius } => match img {
+            DynamicImage::ImageLuma8(gray) => {
+                DynamicImage::ImageLuma8(median_filter(&gray, radius, radius))
+            }
+            other => DynamicImage::ImageRgba8(median_filter(&other.to_rgba8(), radius, radius)),
+        },
+        PreprocessStep::Sharpen { sigma, threshold } => img.unsharpen(sigma, threshold),
+        PreprocessStep::Invert => {
+            let mut img = img;
+            img.invert();
+            img
+        }
+    }
+}
+
+fn auto_contrast(img: DynamicImage) -> DynamicImage {
+    let gray: GrayImage = img.to_luma8();
+    let (mut lo, mut hi) = (u8::MAX, u8::MIN);
+    for Luma([v]) in gray.pixels() {
+        lo = lo.min(*v);
+        hi = hi.max(*v);
+    }
+    if hi <= lo {
+        return img;
+    }
+
+    let scale = 255.0 / f32::from(hi - lo);
+    let stretch = |v: u8| {
+        ((f32::from(v.saturating_sub(lo))) * scale)
+            .round()
+            .min(255.0) as u8
+    };
+
+    match img {
+        DynamicImage::ImageLuma8(mut gray) => {
+            gray.iter_mut().for_each(|v| *v = stretch(*v));
+            DynamicImage::ImageLuma8(gray)
+        }
+        other => {
+            let mut rgba = other.to_rgba8();
+            for pixel in rgba.pixels_mut() {
+                for channel in &mut pixel.0[..3] {
+                    *channel = stretch(*channel);
+                }
+            }
+            DynamicImage::ImageRgba8(rgba)
+        }
+    }
+}
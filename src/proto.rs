@@ -160,6 +160,8 @@ pub struct TextLayoutParagraph {
     pub lines: Vec<TextLayoutLine>,
     #[prost(message, optional, tag = "3")]
     pub geometry: Option<Geometry>,
+    #[prost(string, tag = "5")]
+    pub content_language: String,
 }
 
 #[derive(Clone, PartialEq, Message)]
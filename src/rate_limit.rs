@@ -0,0 +1,134 @@
+use std::{sync::Mutex, time::Duration};
+
+// Tokio's clock, so paused time in tests also drives the bucket.
+use tokio::time::Instant;
+
+/// A request quota: at most `requests` requests every `per`, with bursts up to `requests`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn per_second(requests: u32) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(1),
+        }
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Token-bucket limiter shared by every clone of a [`LensClient`](crate::LensClient).
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let capacity = f64::from(limit.requests.max(1));
+        Self {
+            capacity,
+            refill_per_sec: capacity / limit.per.as_secs_f64().max(f64::EPSILON),
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available and consumes it.
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+                state.last_refill = now;
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - state.tokens) / self.refill_per_sec)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LensClient;
+
+    /// Acquires `count` tokens and returns how long that took.
+    async fn acquire_timed(limiter: &RateLimiter, count: u32) -> Duration {
+        let start = Instant::now();
+        for _ in 0..count {
+            limiter.acquire().await;
+        }
+        start.elapsed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bursts_up_to_capacity_then_waits() {
+        let limiter = RateLimiter::new(RateLimit::per_second(3));
+
+        assert_eq!(acquire_timed(&limiter, 3).await, Duration::ZERO);
+        let waited = acquire_timed(&limiter, 1).await;
+        assert!(
+            waited >= Duration::from_millis(333) && waited < Duration::from_millis(340),
+            "{:?}",
+            waited
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_over_the_period() {
+        let limiter = RateLimiter::new(RateLimit::per_second(4));
+        acquire_timed(&limiter, 4).await;
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(acquire_timed(&limiter, 2).await, Duration::ZERO);
+        assert!(acquire_timed(&limiter, 1).await > Duration::ZERO);
+
+        // A long idle period refills to capacity, not beyond.
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(acquire_timed(&limiter, 4).await, Duration::ZERO);
+        assert!(acquire_timed(&limiter, 1).await > Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clones_share_one_bucket() {
+        let client = LensClient::builder()
+            .rate_limit(RateLimit::per_second(2))
+            .build()
+            .unwrap();
+        let clone = client.clone();
+        let (a, b) = (
+            client.rate_limiter.as_deref().unwrap(),
+            clone.rate_limiter.as_deref().unwrap(),
+        );
+
+        assert_eq!(acquire_timed(a, 2).await, Duration::ZERO);
+        assert_eq!(acquire_timed(b, 1).await, Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,281 @@
+//! Reordering paragraphs and lines to match how a page is read.
+
+use crate::{GeometryData, LensResult, Line, Paragraph, Rect};
+
+/// How paragraphs and lines are ordered in a [`LensResult`] and its `full_text`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadingOrder {
+    /// Whatever order the server returned.
+    #[default]
+    Server,
+    /// Rows top to bottom, each read left to right.
+    LeftToRightRows,
+    /// Columns right to left, each read top to bottom, as in vertical Japanese text.
+    RightToLeftColumns,
+    /// Horizontal bands top to bottom, each read right to left, approximating manga panel
+    /// order. Vertical paragraphs read their lines right to left.
+    MangaPanels,
+}
+
+#[derive(Clone, Copy)]
+enum Layout {
+    RowsLeftToRight,
+    RowsRightToLeft,
+    ColumnsRightToLeft,
+}
+
+impl LensResult {
+    /// Reorders paragraphs and their lines and rebuilds `full_text` and paragraph text.
+    /// Entries without geometry keep their relative order after those with geometry.
+    pub fn reorder(&mut self, order: ReadingOrder) {
+        if order == ReadingOrder::Server {
+            return;
+        }
+
+        let (width, height) = (
+            self.image.original_width.max(1),
+            self.image.original_height.max(1),
+        );
+        let bounds = |g: &Option<GeometryData>| g.as_ref().map(|g| g.bounding_rect(width, height));
+
+        let paragraphs = std::mem::take(&mut self.paragraphs);
+        let mut paragraphs = match order {
+            ReadingOrder::Server => paragraphs,
+            ReadingOrder::LeftToRightRows => {
+                order_in_bands(paragraphs, |p| bounds(&p.geometry), Layout::RowsLeftToRight)
+            }
+            ReadingOrder::RightToLeftColumns => order_in_bands(
+                paragraphs,
+                |p| bounds(&p.geometry),
+                Layout::ColumnsRightToLeft,
+            ),
+            ReadingOrder::MangaPanels => {
+                order_in_bands(paragraphs, |p| bounds(&p.geometry), Layout::RowsRightToLeft)
+            }
+        };
+
+        for paragraph in &mut paragraphs {
+            let vertical = match order {
+                ReadingOrder::RightToLeftColumns => true,
+                ReadingOrder::MangaPanels => is_vertical(paragraph, width, height),
+                _ => false,
+            };
+
+            let layout = if vertical {
+                Layout::ColumnsRightToLeft
+            } else {
+                Layout::RowsLeftToRight
+            };
+            let lines = std::mem::take(&mut paragraph.lines);
+            paragraph.lines = order_in_bands(lines, |l: &Line| bounds(&l.geometry), layout);
+            paragraph.text = paragraph
+                .lines
+                .iter()
+                .map(|l| l.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+
+        self.full_text = paragraphs
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.paragraphs = paragraphs;
+    }
+}
+
+fn is_vertical(paragraph: &Paragraph, width: u32, height: u32) -> bool {
+    let tall_lines = paragraph
+        .lines
+        .iter()
+        .filter_map(|l| l.geometry.as_ref())
+        .map(|g| g.to_pixels(width, height))
+        .filter(|g| g.height > g.width)
+        .count();
+    tall_lines * 2 > paragraph.lines.len()
+}
+
+struct Band<T> {
+    start: f32,
+    end: f32,
+    members: Vec<(Rect, T)>,
+}
+
+/// Splits items into rows (or columns) of overlapping extent, orders the rows top to bottom (or
+/// the columns right to left), then orders the items within each band along it.
+fn order_in_bands<T>(
+    items: Vec<T>,
+    rect_of: impl Fn(&T) -> Option<Rect>,
+    layout: Layout,
+) -> Vec<T> {
+    let mut placed = Vec::new();
+    let mut unplaced = Vec::new();
+    for item in items {
+        match rect_of(&item) {
+            Some(rect) => placed.push((rect, item)),
+            None => unplaced.push(item),
+        }
+    }
+
+    let columns = matches!(layout, Layout::ColumnsRightToLeft);
+    let span = |r: &Rect| {
+        if columns {
+            (r.x, r.right())
+        } else {
+            (r.y, r.bottom())
+        }
+    };
+    // Position along the band: top to bottom within a column, left to right within a row.
+    let along = |r: &Rect| {
+        if columns {
+            r.y + r.height / 2.0
+        } else {
+            r.x + r.width / 2.0
+        }
+    };
+
+    if columns {
+        placed.sort_by(|(a, _), (b, _)| b.right().total_cmp(&a.right()));
+    } else {
+        placed.sort_by(|(a, _), (b, _)| a.y.total_cmp(&b.y));
+    }
+
+    let mut bands: Vec<Band<T>> = Vec::new();
+    for (rect, item) in placed {
+        let (start, end) = span(&rect);
+        match bands.last_mut() {
+            Some(band) if overlaps(band.start, band.end, start, end) => {
+                band.start = band.start.min(start);
+                band.end = band.end.max(end);
+                band.members.push((rect, item));
+            }
+            _ => bands.push(Band {
+                start,
+                end,
+                members: vec![(rect, item)],
+            }),
+        }
+    }
+
+    let mut ordered = Vec::new();
+    for Band { mut members, .. } in bands {
+        members.sort_by(|(a, _), (b, _)| along(a).total_cmp(&along(b)));
+        if matches!(layout, Layout::RowsRightToLeft) {
+            members.reverse();
+        }
+        ordered.extend(members.into_iter().map(|(_, item)| item));
+    }
+    ordered.extend(unplaced);
+    ordered
+}
+
+/// Whether two intervals overlap by more than half of the shorter one.
+fn overlaps(a_start: f32, a_end: f32, b_start: f32, b_end: f32) -> bool {
+    let overlap = a_end.min(b_end) - a_start.max(b_start);
+    let shorter = (a_end - a_start).min(b_end - b_start);
+    overlap > 0.0 && overlap * 2.0 > shorter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{geometry, line, paragraph, result, word};
+
+    /// A single-word line whose box is centered at `(cx, cy)`.
+    fn text_line(text: &str, cx: f32, cy: f32, w: f32, h: f32) -> Line {
+        let g = geometry(cx, cy, w, h, 0.0);
+        line(vec![word(text, "", Some(g.clone()))], Some(g))
+    }
+
+    /// Paragraph `n` of a horizontal page: lines `na` above `nb`, listed bottom first.
+    fn rows(n: &str, cx: f32, cy: f32) -> Paragraph {
+        paragraph(
+            vec![
+                text_line(&format!("{}b", n), cx, cy + 0.05, 0.4, 0.08),
+                text_line(&format!("{}a", n), cx, cy - 0.05, 0.4, 0.08),
+            ],
+            Some(geometry(cx, cy, 0.4, 0.3, 0.0)),
+            None,
+        )
+    }
+
+    /// Paragraph `n` of a vertical page: column `na` right of `nb`, listed left first.
+    fn columns(n: &str, cx: f32, cy: f32) -> Paragraph {
+        paragraph(
+            vec![
+                text_line(&format!("{}b", n), cx - 0.05, cy, 0.08, 0.4),
+                text_line(&format!("{}a", n), cx + 0.05, cy, 0.08, 0.4),
+            ],
+            Some(geometry(cx, cy, 0.3, 0.4, 0.0)),
+            None,
+        )
+    }
+
+    /// Two rows of two horizontal paragraphs, 1 2 over 3 4, in scrambled server order.
+    fn horizontal_grid() -> LensResult {
+        result(
+            vec![
+                rows("4", 0.75, 0.75),
+                rows("1", 0.25, 0.25),
+                rows("3", 0.25, 0.75),
+                rows("2", 0.75, 0.25),
+            ],
+            1000,
+            1000,
+        )
+    }
+
+    /// Two columns of two vertical paragraphs, 1 over 2 on the right and 3 over 4 on the left,
+    /// in scrambled server order.
+    fn vertical_grid() -> LensResult {
+        result(
+            vec![
+                columns("4", 0.25, 0.75),
+                columns("2", 0.75, 0.75),
+                columns("3", 0.25, 0.25),
+                columns("1", 0.75, 0.25),
+            ],
+            1000,
+            1000,
+        )
+    }
+
+    #[test]
+    fn server_order_is_left_alone() {
+        let mut result = horizontal_grid();
+        let before = result.full_text.clone();
+        result.reorder(ReadingOrder::Server);
+        assert_eq!(result.full_text, before);
+    }
+
+    #[test]
+    fn left_to_right_rows() {
+        let mut result = horizontal_grid();
+        result.paragraphs.insert(
+            0,
+            paragraph(vec![line(vec![word("x", "", None)], None)], None, None),
+        );
+        result.reorder(ReadingOrder::LeftToRightRows);
+        assert_eq!(result.full_text, "1a\n1b\n2a\n2b\n3a\n3b\n4a\n4b\nx");
+    }
+
+    #[test]
+    fn right_to_left_columns() {
+        let mut result = vertical_grid();
+        result.reorder(ReadingOrder::RightToLeftColumns);
+        assert_eq!(result.full_text, "1a\n1b\n2a\n2b\n3a\n3b\n4a\n4b");
+        assert_eq!(result.paragraphs[0].text, "1a\n1b");
+    }
+
+    #[test]
+    fn manga_panels_read_rows_right_to_left() {
+        let mut horizontal = horizontal_grid();
+        horizontal.reorder(ReadingOrder::MangaPanels);
+        assert_eq!(horizontal.full_text, "2a\n2b\n1a\n1b\n4a\n4b\n3a\n3b");
+
+        let mut vertical = vertical_grid();
+        vertical.reorder(ReadingOrder::MangaPanels);
+        assert_eq!(vertical.full_text, "1a\n1b\n3a\n3b\n2a\n2b\n4a\n4b");
+    }
+}
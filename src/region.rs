@@ -0,0 +1,253 @@
+//! OCR of a single region of an image, such as one speech bubble.
+
+use image::{DynamicImage, Rgba};
+
+use crate::{
+    ImageSource, LensClient, LensError, LensResult, Point, Rect, Result,
+    image_processor::ImageDimensions, run_blocking,
+};
+
+/// A region of an image, in pixels.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    Rect(Rect),
+    /// A closed polygon. Pixels outside it are painted white before upload.
+    Polygon(Vec<Point>),
+}
+
+impl Region {
+    /// The pixel rectangle to crop, clamped to a `width` x `height` image. `None` if the region
+    /// lies entirely outside the image.
+    fn crop_rect(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let bounds = match self {
+            Region::Rect(rect) => *rect,
+            Region::Polygon(points) => {
+                let first = points.first()?;
+                let (mut min_x, mut min_y, mut max_x, mut max_y) =
+                    (first.x, first.y, first.x, first.y);
+                for p in points {
+                    min_x = min_x.min(p.x);
+                    min_y = min_y.min(p.y);
+                    max_x = max_x.max(p.x);
+                    max_y = max_y.max(p.y);
+                }
+                Rect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+        };
+
+        let left = bounds.x.floor().clamp(0.0, width as f32) as u32;
+        let top = bounds.y.floor().clamp(0.0, height as f32) as u32;
+        let right = bounds.right().ceil().clamp(0.0, width as f32) as u32;
+        let bottom = bounds.bottom().ceil().clamp(0.0, height as f32) as u32;
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        Some((left, top, right - left, bottom - top))
+    }
+}
+
+impl LensClient {
+    /// OCRs only `region` of the image. Geometry in the result is relative to the whole image.
+    pub async fn process_region(
+        &self,
+        source: impl Into<ImageSource>,
+        region: &Region,
+        lang: Option<&str>,
+    ) -> Result<LensResult> {
+        let source = source.into();
+        let job_region = region.clone();
+        let (crop, sub, width, height) =
+            run_blocking(move || crop_region(source, &job_region)).await?;
+
+        let result = self.process_decoded(crop, None, lang).await?;
+        Ok(map_result_from_subimage(result, sub, width, height))
+    }
+}
+
+/// Decodes `source` and cuts out `region`, masked for polygons. Returns the crop, where it lies
+/// in the image, and the image size.
+fn crop_region(source: ImageSource, region: &Region) -> Result<(DynamicImage, Rect, u32, u32)> {
+    let img = source.into_image()?;
+    let (width, height) = (img.width(), img.height());
+
+    let (x, y, crop_w, crop_h) = region
+        .crop_rect(width, height)
+        .ok_or(LensError::InvalidRegion { width, height })?;
+    let mut crop = img.crop_imm(x, y, crop_w, crop_h);
+    if let Region::Polygon(points) = region {
+        crop = mask_polygon(crop, points, x as f32, y as f32);
+    }
+
+    let sub = Rect::new(x as f32, y as f32, crop_w as f32, crop_h as f32);
+    Ok((crop, sub, width, height))
+}
+
+/// Paints every pixel outside `points` white. `points` are in full-image pixels and the crop's
+/// top-left corner is at (`offset_x`, `offset_y`).
+fn mask_polygon(
+    crop: DynamicImage,
+    points: &[Point],
+    offset_x: f32,
+    offset_y: f32,
+) -> DynamicImage {
+    let mut rgba = crop.to_rgba8();
+    for (px, py, pixel) in rgba.enumerate_pixels_mut() {
+        let center = Point {
+            x: offset_x + px as f32 + 0.5,
+            y: offset_y + py as f32 + 0.5,
+        };
+        if !contains(points, center) {
+            *pixel = Rgba([255, 255, 255, 255]);
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Even-odd point-in-polygon test.
+fn contains(points: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = points.len().wrapping_sub(1);
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Rewrites a result for the sub-image `sub` so its geometry and dimensions describe the full
+/// `width` x `height` image.
+pub(crate) fn map_result_from_subimage(
+    mut result: LensResult,
+    sub: Rect,
+    width: u32,
+    height: u32,
+) -> LensResult {
+    result.map_geometry(|g| g.map_from_subimage(sub, width, height));
+
+    let scale_factor = result.image.scale_factor;
+    result.image = ImageDimensions {
+        original_width: width,
+        original_height: height,
+        sent_width: (width as f32 * scale_factor).round() as u32,
+        sent_height: (height as f32 * scale_factor).round() as u32,
+        scale_factor,
+    };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbImage;
+
+    use super::*;
+    use crate::test_support::{geometry, line, paragraph, result, word};
+
+    fn point(x: f32, y: f32) -> Point {
+        Point { x, y }
+    }
+
+    /// A U shape over 0..30 x 0..30 with the notch 10..20 x 0..20 cut out of the top.
+    fn u_shape() -> Vec<Point> {
+        vec![
+            point(0.0, 0.0),
+            point(10.0, 0.0),
+            point(10.0, 20.0),
+            point(20.0, 20.0),
+            point(20.0, 0.0),
+            point(30.0, 0.0),
+            point(30.0, 30.0),
+            point(0.0, 30.0),
+        ]
+    }
+
+    #[test]
+    fn crop_rect_is_clamped_to_the_image() {
+        let partly_outside = Region::Rect(Rect::new(-5.5, 90.2, 20.0, 50.0));
+        assert_eq!(partly_outside.crop_rect(100, 120), Some((0, 90, 15, 30)));
+
+        let outside = Region::Rect(Rect::new(150.0, 10.0, 20.0, 20.0));
+        assert_eq!(outside.crop_rect(100, 120), None);
+        assert_eq!(Region::Polygon(Vec::new()).crop_rect(100, 120), None);
+        assert_eq!(
+            Region::Polygon(u_shape()).crop_rect(100, 120),
+            Some((0, 0, 30, 30))
+        );
+    }
+
+    #[test]
+    fn concave_polygons_exclude_their_notch() {
+        let u = u_shape();
+        assert!(contains(&u, point(5.0, 5.0)));
+        assert!(contains(&u, point(25.0, 5.0)));
+        assert!(contains(&u, point(15.0, 25.0)));
+        assert!(!contains(&u, point(15.0, 5.0)));
+        assert!(!contains(&u, point(35.0, 5.0)));
+    }
+
+    #[test]
+    fn polygon_regions_mask_the_notch_white() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(40, 40));
+        let region = Region::Polygon(u_shape());
+
+        let (crop, sub, width, height) = crop_region(ImageSource::Image(img), &region).unwrap();
+        assert_eq!(
+            (sub, width, height),
+            (Rect::new(0.0, 0.0, 30.0, 30.0), 40, 40)
+        );
+        let crop = crop.to_rgba8();
+        assert_eq!(crop.get_pixel(15, 5), &Rgba([255, 255, 255, 255]));
+        assert_eq!(crop.get_pixel(5, 5), &Rgba([0, 0, 0, 255]));
+        assert_eq!(crop.get_pixel(15, 25), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn regions_outside_the_image_are_rejected() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(40, 40));
+        let region = Region::Rect(Rect::new(50.0, 50.0, 10.0, 10.0));
+        assert!(matches!(
+            crop_region(ImageSource::Image(img), &region),
+            Err(LensError::InvalidRegion {
+                width: 40,
+                height: 40
+            })
+        ));
+    }
+
+    #[test]
+    fn sub_image_results_map_back_onto_the_full_image() {
+        // A word filling the middle of a 100x50 crop taken at (200, 100) of a 400x200 image.
+        let crop_result = result(
+            vec![paragraph(
+                vec![line(
+                    vec![word("x", "", Some(geometry(0.5, 0.5, 0.2, 0.4, 0.0)))],
+                    None,
+                )],
+                None,
+                None,
+            )],
+            100,
+            50,
+        );
+        let sub = Rect::new(200.0, 100.0, 100.0, 50.0);
+
+        let mapped = map_result_from_subimage(crop_result, sub, 400, 200);
+        assert_eq!(
+            (mapped.image.original_width, mapped.image.original_height),
+            (400, 200)
+        );
+        let g = mapped.paragraphs[0].lines[0].words[0]
+            .geometry
+            .clone()
+            .unwrap();
+        let rect = g.bounding_rect(400, 200);
+        assert!((rect.x - 240.0).abs() < 1e-3, "{:?}", rect);
+        assert!((rect.y - 115.0).abs() < 1e-3, "{:?}", rect);
+        assert!((rect.width - 20.0).abs() < 1e-3, "{:?}", rect);
+        assert!((rect.height - 20.0).abs() < 1e-3, "{:?}", rect);
+    }
+}
@@ -0,0 +1,16 @@
+//! Drawing OCR results back onto the image they came from.
+
+pub mod overlay;
+pub mod translation;
+
+use std::path::Path;
+
+pub use ab_glyph::FontArc;
+
+use crate::{LensError, Result};
+
+/// Loads a TrueType or OpenType font for drawing text.
+pub fn load_font<P: AsRef<Path>>(path: P) -> Result<FontArc> {
+    let data = std::fs::read(path)?;
+    FontArc::try_from_vec(data).map_err(|e| LensError::InvalidFont(e.to_string()))
+}
@@ -0,0 +1,306 @@
+//! Debug overlays showing the paragraph, line and word boxes the server detected.
+
+use std::{fmt::Write, io::Cursor, path::Path};
+
+use ab_glyph::{FontArc, PxScale};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_line_segment_mut, draw_text_mut, text_size},
+    rect::Rect as PixelRect,
+};
+
+use crate::{GeometryData, LensError, LensResult, Point, Result, export::escape_xml};
+
+const PARAGRAPH_COLOR: [u8; 3] = [0, 120, 255];
+const LINE_COLOR: [u8; 3] = [0, 190, 0];
+const WORD_COLOR: [u8; 3] = [230, 40, 40];
+const MAX_LABEL_CHARS: usize = 24;
+
+/// Which boxes to draw and whether to label them.
+#[derive(Debug, Clone)]
+pub struct OverlayOptions {
+    pub paragraphs: bool,
+    pub lines: bool,
+    pub words: bool,
+    /// Label each box with its level, index and text.
+    pub labels: bool,
+    /// Font for labels in raster output. Without one, [`draw_overlay`] draws boxes only; SVG
+    /// output leaves fonts to the viewer.
+    pub font: Option<FontArc>,
+}
+
+impl Default for OverlayOptions {
+    fn default() -> Self {
+        Self {
+            paragraphs: true,
+            lines: true,
+            words: true,
+            labels: false,
+            font: None,
+        }
+    }
+}
+
+/// A box to draw: its corners in pixels, color and label.
+struct Shape {
+    corners: [Point; 4],
+    angle_deg: f32,
+    color: [u8; 3],
+    label: String,
+}
+
+/// Draws the selected boxes onto a copy of `img`.
+pub fn draw_overlay(
+    img: &DynamicImage,
+    result: &LensResult,
+    options: &OverlayOptions,
+) -> RgbaImage {
+    let mut canvas = img.to_rgba8();
+    let (width, height) = canvas.dimensions();
+    let shapes = shapes(result, width, height, options);
+    let thickness = stroke_width(width, height);
+
+    for shape in &shapes {
+        let color = rgba(shape.color);
+        for i in 0..4 {
+            let (a, b) = (shape.corners[i], shape.corners[(i + 1) % 4]);
+            // Thicken the outline by repeating it at small offsets.
+            for offset in 0..thickness {
+                let d = offset as f32 - (thickness - 1) as f32 / 2.0;
+                draw_line_segment_mut(&mut canvas, (a.x + d, a.y), (b.x + d, b.y), color);
+                draw_line_segment_mut(&mut canvas, (a.x, a.y + d), (b.x, b.y + d), color);
+            }
+        }
+    }
+
+    if options.labels
+        && let Some(font) = &options.font
+    {
+        let scale = PxScale::from(label_size(width, height));
+        for shape in &shapes {
+            let (text_w, text_h) = text_size(scale, font, &shape.label);
+            let top_left = shape.corners[0];
+            let x = top_left.x.round() as i32;
+            // Above the box when there is room, otherwise just inside it.
+            let y = if top_left.y >= text_h as f32 {
+                top_left.y.round() as i32 - text_h as i32
+            } else {
+                top_left.y.round() as i32
+            };
+
+            draw_filled_rect_mut(
+                &mut canvas,
+                PixelRect::at(x, y).of_size(text_w.max(1), text_h.max(1)),
+                rgba(shape.color),
+            );
+            draw_text_mut(
+                &mut canvas,
+                Rgba([255, 255, 255, 255]),
+                x,
+                y,
+                scale,
+                font,
+                &shape.label,
+            );
+        }
+    }
+
+    canvas
+}
+
+/// Renders the selected boxes as an SVG document with `img` embedded as a PNG.
+pub fn overlay_svg(
+    img: &DynamicImage,
+    result: &LensResult,
+    options: &OverlayOptions,
+) -> Result<String> {
+    let (width, height) = img.dimensions();
+    let mut png = Cursor::new(Vec::new());
+    img.write_to(&mut png, ImageFormat::Png)
+        .map_err(LensError::ImageEncode)?;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\">",
+        width, height
+    );
+    let _ = writeln!(
+        svg,
+        "<image width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+        width,
+        height,
+        BASE64.encode(png.into_inner())
+    );
+
+    let shapes = shapes(result, width, height, options);
+    let stroke = stroke_width(width, height);
+    for shape in &shapes {
+        let points = shape
+            .corners
+            .iter()
+            .map(|p| format!("{:.1},{:.1}", p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(
+            svg,
+            "<polygon points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            points,
+            hex_color(shape.color),
+            stroke
+        );
+    }
+
+    if options.labels {
+        let size = label_size(width, height);
+        for shape in &shapes {
+            let p = shape.corners[0];
+            let _ = writeln!(
+                svg,
+                "<text x=\"{x:.1}\" y=\"{y:.1}\" transform=\"rotate({a:.2} {x:.1} {y:.1})\" \
+                 font-size=\"{s:.1}\" font-family=\"sans-serif\" fill=\"{c}\">{t}</text>",
+                x = p.x,
+                y = p.y - 2.0,
+                a = shape.angle_deg,
+                s = size,
+                c = hex_color(shape.color),
+                t = escape_xml(&shape.label)
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Writes an overlay to `path`: SVG for a `.svg` extension, otherwise a raster image in the
+/// format the extension names.
+pub fn save_overlay<P: AsRef<Path>>(
+    img: &DynamicImage,
+    result: &LensResult,
+    path: P,
+    options: &OverlayOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    let is_svg = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+
+    if is_svg {
+        std::fs::write(path, overlay_svg(img, result, options)?)?;
+    } else {
+        draw_overlay(img, result, options)
+            .save(path)
+            .map_err(LensError::ImageEncode)?;
+    }
+    Ok(())
+}
+
+/// The boxes to draw, paragraphs first so smaller boxes stay on top.
+fn shapes(result: &LensResult, width: u32, height: u32, options: &OverlayOptions) -> Vec<Shape> {
+    let shape = |g: &GeometryData, color, prefix: char, index: usize, text: &str| Shape {
+        corners: g.corners(width, height),
+        angle_deg: g.angle_deg,
+        color,
+        label: label(prefix, index, text),
+    };
+
+    let mut paragraphs = Vec::new();
+    let mut lines = Vec::new();
+    let mut words = Vec::new();
+    for (p_index, paragraph) in result.paragraphs.iter().enumerate() {
+        if options.paragraphs
+            && let Some(g) = &paragraph.geometry
+        {
+            paragraphs.push(shape(g, PARAGRAPH_COLOR, 'P', p_index + 1, &paragraph.text));
+        }
+        for line in &paragraph.lines {
+            if options.lines
+                && let Some(g) = &line.geometry
+            {
+                lines.push(shape(g, LINE_COLOR, 'L', lines.len() + 1, &line.text));
+            }
+            for word in &line.words {
+                if options.words
+                    && let Some(g) = &word.geometry
+                {
+                    words.push(shape(g, WORD_COLOR, 'W', words.len() + 1, &word.text));
+                }
+            }
+        }
+    }
+
+    paragraphs.extend(lines);
+    paragraphs.extend(words);
+    paragraphs
+}
+
+/// `P3 first words of the text…`, with line breaks flattened.
+fn label(prefix: char, index: usize, text: &str) -> String {
+    let flat = text.replace('\n', " ");
+    let mut label = format!("{}{} ", prefix, index);
+    label.extend(flat.chars().take(MAX_LABEL_CHARS));
+    if flat.chars().count() > MAX_LABEL_CHARS {
+        label.push('…');
+    }
+    label
+}
+
+fn stroke_width(width: u32, height: u32) -> u32 {
+    (width.min(height) / 400).max(1)
+}
+
+fn label_size(width: u32, height: u32) -> f32 {
+    (width.min(height) as f32 / 60.0).max(12.0)
+}
+
+fn rgba(color: [u8; 3]) -> Rgba<u8> {
+    Rgba([color[0], color[1], color[2], 255])
+}
+
+fn hex_color(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::fixture;
+
+    #[test]
+    fn labels_are_flattened_and_truncated() {
+        assert_eq!(label('P', 3, "a\nb"), "P3 a b");
+
+        let exact = "x".repeat(MAX_LABEL_CHARS);
+        assert_eq!(label('L', 1, &exact), format!("L1 {}", exact));
+
+        // Counted in characters, so multi-byte text is not cut mid-character.
+        let long = "語".repeat(MAX_LABEL_CHARS + 5);
+        assert_eq!(
+            label('W', 12, &long),
+            format!("W12 {}…", "語".repeat(MAX_LABEL_CHARS))
+        );
+    }
+
+    #[test]
+    fn svg_has_one_polygon_per_box() {
+        let img = DynamicImage::new_rgb8(80, 60);
+        let polygons = |options: &OverlayOptions| {
+            overlay_svg(&img, &fixture(), options)
+                .unwrap()
+                .matches("<polygon ")
+                .count()
+        };
+
+        // The fixture places one paragraph, one line and two words.
+        assert_eq!(polygons(&OverlayOptions::default()), 4);
+        let words_only = OverlayOptions {
+            paragraphs: false,
+            lines: false,
+            ..Default::default()
+        };
+        assert_eq!(polygons(&words_only), 2);
+    }
+}
@@ -0,0 +1,445 @@
+//! In-place translation: each paragraph's box is painted over and its translation drawn inside.
+
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+
+use crate::{
+    GeometryData, LensResult,
+    constants::{DEFAULT_TRANSLATION_MAX_FONT_SIZE, DEFAULT_TRANSLATION_MIN_FONT_SIZE},
+};
+
+/// How a paragraph's box is cleared before its translation is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoxFill {
+    /// The median color just outside the box, which blends into speech bubbles and plain
+    /// backgrounds.
+    #[default]
+    SampleBackground,
+    Solid([u8; 3]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextDirection {
+    /// Vertical for boxes taller than wide whose translation is mostly CJK, horizontal otherwise.
+    #[default]
+    Auto,
+    Horizontal,
+    /// Columns read top to bottom, right to left.
+    Vertical,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranslationRenderOptions {
+    pub font: FontArc,
+    pub fill: BoxFill,
+    /// Text color; black or white, whichever contrasts with the fill, when `None`.
+    pub text_color: Option<[u8; 3]>,
+    pub direction: TextDirection,
+    /// Font size bounds in pixels. Text is drawn at the largest size that fits its box.
+    pub min_font_size: f32,
+    pub max_font_size: f32,
+}
+
+impl TranslationRenderOptions {
+    pub fn new(font: FontArc) -> Self {
+        Self {
+            font,
+            fill: BoxFill::default(),
+            text_color: None,
+            direction: TextDirection::default(),
+            min_font_size: DEFAULT_TRANSLATION_MIN_FONT_SIZE,
+            max_font_size: DEFAULT_TRANSLATION_MAX_FONT_SIZE,
+        }
+    }
+}
+
+/// Draws the result's own translation over `img`. See [`LensResult::paragraph_translations`].
+pub fn render_translation(
+    img: &DynamicImage,
+    result: &LensResult,
+    options: &TranslationRenderOptions,
+) -> RgbaImage {
+    render_translations(img, result, &result.paragraph_translations(), options)
+}
+
+/// Draws `translations[i]` over paragraph `i` of `result`. Paragraphs without geometry or with
+/// an empty translation are left untouched.
+pub fn render_translations(
+    img: &DynamicImage,
+    result: &LensResult,
+    translations: &[String],
+    options: &TranslationRenderOptions,
+) -> RgbaImage {
+    let mut canvas = img.to_rgba8();
+    let (width, height) = canvas.dimensions();
+
+    for (paragraph, text) in result.paragraphs.iter().zip(translations) {
+        let text = text.trim();
+        let Some(g) = &paragraph.geometry else {
+            continue;
+        };
+        if text.is_empty() {
+            continue;
+        }
+
+        let px = g.to_pixels(width, height);
+        let (box_w, box_h) = (px.width.round() as u32, px.height.round() as u32);
+        if box_w < 2 || box_h < 2 {
+            continue;
+        }
+
+        let fill = match options.fill {
+            BoxFill::Solid(color) => color,
+            BoxFill::SampleBackground => sample_background(&canvas, g),
+        };
+        let text_color = options.text_color.unwrap_or_else(|| contrasting(fill));
+        let vertical = match options.direction {
+            TextDirection::Horizontal => false,
+            TextDirection::Vertical => true,
+            TextDirection::Auto => box_h > box_w && is_mostly_cjk(text),
+        };
+
+        let mut layer = RgbaImage::from_pixel(box_w, box_h, rgba(fill));
+        if vertical {
+            draw_vertical(&mut layer, text, rgba(text_color), options);
+        } else {
+            draw_horizontal(&mut layer, text, rgba(text_color), options);
+        }
+        composite_rotated(&mut canvas, &layer, &px);
+    }
+
+    canvas
+}
+
+/// Wraps `text` into centered lines at the largest size that fits `layer`.
+fn draw_horizontal(
+    layer: &mut RgbaImage,
+    text: &str,
+    color: Rgba<u8>,
+    options: &TranslationRenderOptions,
+) {
+    let font = &options.font;
+    let (width, height) = layer.dimensions();
+    let pad = padding(width, height);
+    let (area_w, area_h) = (width - 2 * pad, height - 2 * pad);
+
+    let measure = |size: f32| move |s: &str| text_size(size, font, s).0 as f32;
+    let fits = |size: f32| {
+        let lines = wrap(text, area_w as f32, measure(size));
+        let widest = lines
+            .iter()
+            .map(|l| text_size(size, font, l).0)
+            .max()
+            .unwrap_or(0);
+        lines.len() as f32 * line_height(font, size) <= area_h as f32 && widest <= area_w
+    };
+    let size = largest_fitting_size(options, area_h as f32, fits);
+    let lines = wrap(text, area_w as f32, measure(size));
+
+    let step = line_height(font, size);
+    let block_h = lines.len() as f32 * step;
+    let top = pad as f32 + (area_h as f32 - block_h).max(0.0) / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        let line_w = text_size(size, font, line).0;
+        let x = pad as i32 + (area_w as i32 - line_w as i32) / 2;
+        let y = (top + i as f32 * step).round() as i32;
+        draw_text_mut(layer, color, x, y, size, font, line);
+    }
+}
+
+/// Stacks characters into columns, right to left, at the largest size that fits `layer`.
+fn draw_vertical(
+    layer: &mut RgbaImage,
+    text: &str,
+    color: Rgba<u8>,
+    options: &TranslationRenderOptions,
+) {
+    let font = &options.font;
+    let (width, height) = layer.dimensions();
+    let pad = padding(width, height);
+    let (area_w, area_h) = (width - 2 * pad, height - 2 * pad);
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let layout = |size: f32| {
+        let per_column = ((area_h as f32 / size).floor() as usize).max(1);
+        let columns = chars.len().div_ceil(per_column);
+        (per_column, columns)
+    };
+    let fits = |size: f32| {
+        let (per_column, columns) = layout(size);
+        per_column as f32 * size <= area_h as f32 && columns as f32 * size <= area_w as f32
+    };
+    let size = largest_fitting_size(options, area_w.min(area_h) as f32, fits);
+    let (per_column, columns) = layout(size);
+
+    let block_w = columns as f32 * size;
+    let block_h = per_column.min(chars.len()) as f32 * size;
+    let right = pad as f32 + (area_w as f32 + block_w) / 2.0;
+    let top = pad as f32 + (area_h as f32 - block_h).max(0.0) / 2.0;
+    let scaled = font.as_scaled(PxScale::from(size));
+
+    for (i, c) in chars.iter().enumerate() {
+        let (column, row) = (i / per_column, i % per_column);
+        let advance = scaled.h_advance(scaled.glyph_id(*c));
+        let x = right - (column + 1) as f32 * size + (size - advance) / 2.0;
+        let y = top + row as f32 * size;
+        draw_text_mut(
+            layer,
+            color,
+            x.round() as i32,
+            y.round() as i32,
+            size,
+            font,
+            &c.to_string(),
+        );
+    }
+}
+
+/// Binary-searches the largest font size in the configured range for which `fits` holds,
+/// falling back to the minimum.
+fn largest_fitting_size(
+    options: &TranslationRenderOptions,
+    limit: f32,
+    fits: impl Fn(f32) -> bool,
+) -> f32 {
+    let (mut low, mut high) = (
+        options.min_font_size,
+        options.max_font_size.min(limit).max(options.min_font_size),
+    );
+    if fits(high) {
+        return high;
+    }
+    for _ in 0..12 {
+        let mid = (low + high) / 2.0;
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Greedy line breaking to lines at most `max_width` wide, as reported by `measure`. Breaks at
+/// spaces and between CJK characters, and splits words that are wider than a line on their own.
+fn wrap(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for (token, spaced) in tokens(paragraph) {
+            let candidate = if line.is_empty() {
+                token.clone()
+            } else if spaced {
+                format!("{} {}", line, token)
+            } else {
+                format!("{}{}", line, token)
+            };
+
+            if measure(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            if measure(&token) <= max_width {
+                line = token;
+                continue;
+            }
+
+            for c in token.chars() {
+                let mut next = line.clone();
+                next.push(c);
+                if !line.is_empty() && measure(&next) > max_width {
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                } else {
+                    line = next;
+                }
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Words and single CJK characters, each with whether whitespace preceded it.
+fn tokens(text: &str) -> Vec<(String, bool)> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut spaced = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), spaced));
+            }
+            spaced = true;
+        } else if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), spaced));
+                spaced = false;
+            }
+            tokens.push((c.to_string(), spaced));
+            spaced = false;
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push((word, spaced));
+    }
+    tokens
+}
+
+fn line_height(font: &FontArc, size: f32) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    scaled.height() + scaled.line_gap()
+}
+
+fn padding(width: u32, height: u32) -> u32 {
+    ((width.min(height) as f32 * 0.06) as u32).min(width.min(height) / 2 - 1)
+}
+
+/// Copies `layer`, an upright rendering of the box `px` (in pixels), onto `canvas` rotated into
+/// place.
+fn composite_rotated(canvas: &mut RgbaImage, layer: &RgbaImage, px: &GeometryData) {
+    let (canvas_w, canvas_h) = canvas.dimensions();
+    let (layer_w, layer_h) = layer.dimensions();
+    let (sin, cos) = px.rotation_z.sin_cos();
+
+    // Half extents of the rotated box's bounding rectangle.
+    let half_w = (px.width * cos.abs() + px.height * sin.abs()) / 2.0;
+    let half_h = (px.width * sin.abs() + px.height * cos.abs()) / 2.0;
+    let x0 = (px.center_x - half_w).floor().max(0.0) as u32;
+    let y0 = (px.center_y - half_h).floor().max(0.0) as u32;
+    let x1 = ((px.center_x + half_w).ceil().max(0.0) as u32).min(canvas_w);
+    let y1 = ((px.center_y + half_h).ceil().max(0.0) as u32).min(canvas_h);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let (dx, dy) = (x as f32 + 0.5 - px.center_x, y as f32 + 0.5 - px.center_y);
+            let lx = dx * cos + dy * sin + layer_w as f32 / 2.0;
+            let ly = -dx * sin + dy * cos + layer_h as f32 / 2.0;
+            if lx >= 0.0 && ly >= 0.0 && lx < layer_w as f32 && ly < layer_h as f32 {
+                canvas.put_pixel(x, y, *layer.get_pixel(lx as u32, ly as u32));
+            }
+        }
+    }
+}
+
+/// The per-channel median of the pixels along a ring just outside the box `g`.
+fn sample_background(canvas: &RgbaImage, g: &GeometryData) -> [u8; 3] {
+    let (width, height) = canvas.dimensions();
+    let grown = GeometryData {
+        width: g.width + 4.0 / width as f32,
+        height: g.height + 4.0 / height as f32,
+        ..g.clone()
+    };
+    let corners = grown.corners(width, height);
+
+    let mut channels: [Vec<u8>; 3] = Default::default();
+    for i in 0..4 {
+        let (a, b) = (corners[i], corners[(i + 1) % 4]);
+        let steps = (b.x - a.x).hypot(b.y - a.y).ceil().max(1.0) as usize;
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            let x = (a.x + (b.x - a.x) * t)
+                .round()
+                .clamp(0.0, (width - 1) as f32) as u32;
+            let y = (a.y + (b.y - a.y) * t)
+                .round()
+                .clamp(0.0, (height - 1) as f32) as u32;
+            let pixel = canvas.get_pixel(x, y);
+            for (channel, values) in channels.iter_mut().enumerate() {
+                values.push(pixel[channel]);
+            }
+        }
+    }
+
+    channels.map(|mut values| {
+        values.sort_unstable();
+        values[values.len() / 2]
+    })
+}
+
+fn contrasting(color: [u8; 3]) -> [u8; 3] {
+    let luma = 0.299 * color[0] as f32 + 0.587 * color[1] as f32 + 0.114 * color[2] as f32;
+    if luma > 128.0 {
+        [0, 0, 0]
+    } else {
+        [255, 255, 255]
+    }
+}
+
+fn rgba(color: [u8; 3]) -> Rgba<u8> {
+    Rgba([color[0], color[1], color[2], 255])
+}
+
+fn is_mostly_cjk(text: &str) -> bool {
+    let letters = text.chars().filter(|c| c.is_alphanumeric()).count();
+    let cjk = text.chars().filter(|&c| is_cjk(c)).count();
+    cjk * 2 > letters
+}
+
+/// Hiragana, katakana, CJK ideographs, hangul and full-width forms.
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF
+        | 0x3000..=0x30FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFFEF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is one unit wide.
+    fn chars(s: &str) -> f32 {
+        s.chars().count() as f32
+    }
+
+    #[test]
+    fn tokens_split_cjk_per_character() {
+        assert_eq!(
+            tokens("hello 世界 ok"),
+            [
+                ("hello".to_string(), false),
+                ("世".to_string(), true),
+                ("界".to_string(), false),
+                ("ok".to_string(), true),
+            ]
+        );
+        assert_eq!(
+            tokens("abc漢字def"),
+            [
+                ("abc".to_string(), false),
+                ("漢".to_string(), false),
+                ("字".to_string(), false),
+                ("def".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces_and_between_cjk_characters() {
+        assert_eq!(wrap("one two three", 7.0, chars), ["one two", "three"]);
+        assert_eq!(wrap("日本語の文章", 4.0, chars), ["日本語の", "文章"]);
+        assert_eq!(wrap("first\nsecond", 20.0, chars), ["first", "second"]);
+    }
+
+    #[test]
+    fn wrap_splits_words_longer_than_a_line() {
+        assert_eq!(
+            wrap("a internationalization b", 8.0, chars),
+            ["a", "internat", "ionaliza", "tion b"]
+        );
+    }
+}
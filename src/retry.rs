@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::{
+    constants::{
+        DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_MAX_ATTEMPTS, DEFAULT_RETRY_MAX_DELAY_MS,
+    },
+    error::LensError,
+};
+
+/// Controls how [`LensClient`](crate::LensClient) retries failed uploads.
+///
+/// The delay before retry `n` (1-based) is `base_delay * 2^(n - 1)`, capped at `max_delay`, then
+/// reduced by a random fraction of up to `jitter`. When the server sends `Retry-After` and
+/// `honor_retry_after` is set, that value is used instead, still capped at `max_delay` so a
+/// misbehaving server cannot stall the client. Only the delta-seconds form (`Retry-After: 120`)
+/// is understood; an HTTP-date falls back to the computed backoff.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction of the computed delay that may be randomly removed, in `0.0..=1.0`.
+    pub jitter: f64,
+    /// HTTP statuses that are retried.
+    pub retryable_statuses: Vec<StatusCode>,
+    /// Retry when the request times out.
+    pub retry_on_timeout: bool,
+    /// Retry on connection failures and resets.
+    pub retry_on_transport: bool,
+    pub honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_RETRY_MAX_DELAY_MS),
+            jitter: 0.5,
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_on_timeout: true,
+            retry_on_transport: true,
+            honor_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes exactly one attempt.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_retryable(&self, err: &LensError) -> bool {
+        match err {
+            LensError::RateLimited { .. } => self
+                .retryable_statuses
+                .contains(&StatusCode::TOO_MANY_REQUESTS),
+            LensError::Http { status, .. } => self.retryable_statuses.contains(status),
+            LensError::Timeout(_) => self.retry_on_timeout,
+            LensError::Transport(e) => {
+                self.retry_on_transport && (e.is_connect() || e.is_request() || e.is_body())
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay before the retry that follows failed attempt number `attempt` (1-based).
+    pub fn delay_for(&self, attempt: u32, err: &LensError) -> Duration {
+        if self.honor_retry_after
+            && let Some(retry_after) = err.retry_after()
+        {
+            return retry_after.min(self.max_delay);
+        }
+
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let capped = exp.min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return capped;
+        }
+        capped.mul_f64(1.0 - jitter * rand::random::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+            jitter,
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn http(status: StatusCode, retry_after: Option<Duration>) -> LensError {
+        LensError::Http {
+            status,
+            body: String::new(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_until_the_cap() {
+        let policy = policy(0.0);
+        let err = http(StatusCode::SERVICE_UNAVAILABLE, None);
+        let delays: Vec<_> = (1..=6)
+            .map(|attempt| policy.delay_for(attempt, &err).as_millis())
+            .collect();
+
+        assert_eq!(delays, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(
+            policy.delay_for(u32::MAX, &err),
+            Duration::from_millis(1_000)
+        );
+    }
+
+    #[test]
+    fn jitter_only_shortens_the_delay() {
+        let policy = policy(0.5);
+        let err = http(StatusCode::SERVICE_UNAVAILABLE, None);
+        for _ in 0..200 {
+            let delay = policy.delay_for(3, &err);
+            assert!(delay >= Duration::from_millis(200), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(400), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn retry_after_is_honored_up_to_max_delay() {
+        let policy = policy(0.0);
+        let short = http(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some(Duration::from_millis(700)),
+        );
+        let long = LensError::RateLimited {
+            retry_after: Some(Duration::from_secs(86_400)),
+            body: String::new(),
+        };
+
+        assert_eq!(policy.delay_for(1, &short), Duration::from_millis(700));
+        assert_eq!(policy.delay_for(1, &long), Duration::from_millis(1_000));
+
+        let ignoring = RetryPolicy {
+            honor_retry_after: false,
+            ..policy
+        };
+        assert_eq!(ignoring.delay_for(1, &short), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn only_configured_failures_are_retryable() {
+        let policy = RetryPolicy::default();
+        let rate_limited = LensError::RateLimited {
+            retry_after: None,
+            body: String::new(),
+        };
+
+        assert!(policy.is_retryable(&rate_limited));
+        assert!(policy.is_retryable(&http(StatusCode::BAD_GATEWAY, None)));
+        assert!(!policy.is_retryable(&http(StatusCode::BAD_REQUEST, None)));
+        assert!(!policy.is_retryable(&LensError::EmptyResult));
+        assert!(!policy.is_retryable(&LensError::Config("bad".to_string())));
+
+        let no_429 = RetryPolicy {
+            retryable_statuses: vec![StatusCode::SERVICE_UNAVAILABLE],
+            ..RetryPolicy::default()
+        };
+        assert!(!no_429.is_retryable(&rate_limited));
+    }
+}
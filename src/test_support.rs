@@ -0,0 +1,82 @@
+//! Builders for `LensResult` fixtures in unit tests.
+
+use crate::{GeometryData, ImageDimensions, LensResult, Line, Paragraph, Word};
+
+/// A box in normalized coordinates rotated `angle_deg` clockwise.
+pub fn geometry(
+    center_x: f32,
+    center_y: f32,
+    width: f32,
+    height: f32,
+    angle_deg: f32,
+) -> GeometryData {
+    GeometryData {
+        center_x,
+        center_y,
+        width,
+        height,
+        rotation_z: angle_deg.to_radians(),
+        angle_deg,
+    }
+}
+
+pub fn word(text: &str, separator: &str, geometry: Option<GeometryData>) -> Word {
+    Word {
+        text: text.to_string(),
+        separator: separator.to_string(),
+        geometry,
+    }
+}
+
+/// A line whose text is its words joined with their separators.
+pub fn line(words: Vec<Word>, geometry: Option<GeometryData>) -> Line {
+    let text = words
+        .iter()
+        .map(|w| format!("{}{}", w.text, w.separator))
+        .collect::<String>()
+        .trim_end()
+        .to_string();
+    Line {
+        text,
+        words,
+        geometry,
+    }
+}
+
+pub fn paragraph(
+    lines: Vec<Line>,
+    geometry: Option<GeometryData>,
+    language: Option<&str>,
+) -> Paragraph {
+    Paragraph {
+        text: lines
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        lines,
+        geometry,
+        language: language.map(str::to_string),
+    }
+}
+
+/// A result for a `width` x `height` image that was sent unscaled.
+pub fn result(paragraphs: Vec<Paragraph>, width: u32, height: u32) -> LensResult {
+    LensResult {
+        full_text: paragraphs
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        paragraphs,
+        translation: None,
+        language: None,
+        image: ImageDimensions {
+            original_width: width,
+            original_height: height,
+            sent_width: width,
+            sent_height: height,
+            scale_factor: 1.0,
+        },
+    }
+}
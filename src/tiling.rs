@@ -0,0 +1,523 @@
+//! Splits oversized images into overlapping tiles and stitches the results back together.
+
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use image::DynamicImage;
+
+use crate::{
+    GeometryData, LensClient, LensError, LensResult, Line, Paragraph, Rect, Result,
+    constants::{DEFAULT_IMAGE_MAX_DIMENSION, DEFAULT_TILE_OVERLAP, DEFAULT_TILE_PARALLELISM},
+    image_processor::{self, ImageDimensions},
+    run_blocking,
+};
+
+/// Opt-in tiling for images larger than the server can read at full resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilingOptions {
+    /// Maximum width and height of a tile, in original-image pixels.
+    pub tile_size: u32,
+    /// Pixels shared by neighboring tiles, so text on a seam appears whole in at least one tile.
+    pub overlap: u32,
+    /// Maximum number of tiles prepared and uploaded at once. Tall strips can have dozens of
+    /// tiles, and sending them all together invites rate limiting.
+    pub parallelism: usize,
+}
+
+impl Default for TilingOptions {
+    fn default() -> Self {
+        Self {
+            tile_size: DEFAULT_IMAGE_MAX_DIMENSION,
+            overlap: DEFAULT_TILE_OVERLAP,
+            parallelism: DEFAULT_TILE_PARALLELISM,
+        }
+    }
+}
+
+impl TilingOptions {
+    pub fn needs_tiling(&self, width: u32, height: u32) -> bool {
+        width > self.tile_size || height > self.tile_size
+    }
+
+    /// Checks that tiles have a size and that each one advances past its overlap.
+    pub fn validate(&self) -> Result<()> {
+        if self.tile_size == 0 {
+            return Err(LensError::Config("tile_size must be positive".to_string()));
+        }
+        if self.overlap >= self.tile_size {
+            return Err(LensError::Config(format!(
+                "tile overlap {} must be less than tile_size {}",
+                self.overlap, self.tile_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// The tiles covering a `width` x `height` image, in row-major order. Fails when the
+    /// options do not [`validate`](Self::validate).
+    pub fn tiles(&self, width: u32, height: u32) -> Result<Vec<Tile>> {
+        self.validate()?;
+        let xs = axis_offsets(width, self.tile_size, self.overlap);
+        let ys = axis_offsets(height, self.tile_size, self.overlap);
+
+        let mut tiles = Vec::with_capacity(xs.len() * ys.len());
+        for (row, &(y, h)) in ys.iter().enumerate() {
+            for (col, &(x, w)) in xs.iter().enumerate() {
+                // Each tile owns the area up to the middle of its overlaps with its neighbors.
+                let own_left = if col == 0 { 0 } else { x + self.overlap / 2 };
+                let own_top = if row == 0 { 0 } else { y + self.overlap / 2 };
+                let own_right = match xs.get(col + 1) {
+                    Some(&(next_x, _)) => next_x + self.overlap / 2,
+                    None => width,
+                };
+                let own_bottom = match ys.get(row + 1) {
+                    Some(&(next_y, _)) => next_y + self.overlap / 2,
+                    None => height,
+                };
+
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                    owned: (own_left, own_top, own_right, own_bottom),
+                });
+            }
+        }
+        Ok(tiles)
+    }
+}
+
+/// One tile of a larger image, in original-image pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// `(left, top, right, bottom)` of the region whose words this tile keeps.
+    pub owned: (u32, u32, u32, u32),
+}
+
+/// `(start, length)` of each tile along one axis. Requires `overlap < tile_size`.
+fn axis_offsets(length: u32, tile_size: u32, overlap: u32) -> Vec<(u32, u32)> {
+    if length <= tile_size {
+        return vec![(0, length)];
+    }
+
+    let step = tile_size - overlap;
+    let mut offsets = Vec::new();
+    let mut start = 0;
+    loop {
+        if start + tile_size >= length {
+            // Align the last tile with the far edge instead of leaving a sliver.
+            offsets.push((length - tile_size, tile_size));
+            break;
+        }
+        offsets.push((start, tile_size));
+        start += step;
+    }
+    offsets
+}
+
+impl LensClient {
+    /// OCRs `img` tile by tile and merges the results into one [`LensResult`] whose geometry is
+    /// relative to the whole image. Words in overlapping areas are kept from one tile only.
+    ///
+    /// The server translates each tile as a whole, so a paragraph's translation is kept from
+    /// the tile that owns the paragraph's center. Paragraphs wider than the overlap are cut off
+    /// in both tiles and may still have their translation split or repeated.
+    pub async fn process_image_tiled(
+        &self,
+        img: DynamicImage,
+        lang: Option<&str>,
+        options: &TilingOptions,
+    ) -> Result<LensResult> {
+        let (width, height) = (img.width(), img.height());
+        let tiles = options.tiles(width, height)?;
+
+        let img = Arc::new(img);
+
+        let jobs = tiles.clone().into_iter().map(|tile| {
+            let img = Arc::clone(&img);
+            let options = self.image_options.clone();
+            async move {
+                let processed = run_blocking(move || {
+                    let crop = img.crop_imm(tile.x, tile.y, tile.width, tile.height);
+                    image_processor::process_image_with_options(crop, &options)
+                })
+                .await?;
+                self.send_request(processed, lang).await
+            }
+        });
+        let results = stream::iter(jobs)
+            .buffered(options.parallelism.max(1))
+            .collect()
+            .await;
+
+        merge_tiles(&tiles, results, width, height)
+    }
+}
+
+/// Stitches per-tile results for a `width` x `height` image into one result.
+fn merge_tiles(
+    tiles: &[Tile],
+    results: Vec<Result<LensResult>>,
+    width: u32,
+    height: u32,
+) -> Result<LensResult> {
+    let mut paragraphs = Vec::new();
+    let mut translations = Vec::new();
+    let mut language = None;
+    let mut scale_factor = None;
+
+    for (tile, result) in tiles.iter().zip(results) {
+        let result = match result {
+            Ok(result) => result,
+            Err(LensError::EmptyResult) => continue,
+            Err(e) => return Err(e),
+        };
+
+        scale_factor.get_or_insert(result.image.scale_factor);
+        if language.is_none() {
+            language.clone_from(&result.language);
+        }
+        if result.paragraphs.is_empty()
+            && let Some(translation) = &result.translation
+        {
+            translations.push(translation.clone());
+        }
+
+        let tile_translations = result.paragraph_translations();
+        for (paragraph, translation) in result.paragraphs.into_iter().zip(tile_translations) {
+            let owns_center = paragraph.geometry.as_ref().is_none_or(|g| {
+                is_owned(&tile_to_image(g, tile, width, height), tile, width, height)
+            });
+            if owns_center && !translation.is_empty() {
+                translations.push(translation);
+            }
+            paragraphs.extend(stitch_paragraph(paragraph, tile, width, height));
+        }
+    }
+
+    let translation = if translations.is_empty() {
+        None
+    } else {
+        Some(translations.join("\n"))
+    };
+    if paragraphs.is_empty() && translation.is_none() {
+        return Err(LensError::EmptyResult);
+    }
+
+    let full_text = paragraphs
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let scale_factor = scale_factor.unwrap_or(1.0);
+
+    Ok(LensResult {
+        full_text,
+        paragraphs,
+        translation,
+        language,
+        image: ImageDimensions {
+            original_width: width,
+            original_height: height,
+            sent_width: (width as f32 * scale_factor).round() as u32,
+            sent_height: (height as f32 * scale_factor).round() as u32,
+            scale_factor,
+        },
+    })
+}
+
+/// Maps a tile's paragraph into whole-image coordinates, dropping words the tile does not own.
+/// Lines and paragraphs that lose words are shrunk to the words that remain.
+fn stitch_paragraph(
+    mut paragraph: Paragraph,
+    tile: &Tile,
+    width: u32,
+    height: u32,
+) -> Option<Paragraph> {
+    let to_image = |g: &GeometryData| tile_to_image(g, tile, width, height);
+    let mut trimmed = false;
+
+    paragraph.lines = paragraph
+        .lines
+        .into_iter()
+        .filter_map(|mut line| {
+            let word_count = line.words.len();
+            line.words.retain(|w| match &w.geometry {
+                Some(g) => is_owned(&to_image(g), tile, width, height),
+                None => true,
+            });
+            if line.words.is_empty() {
+                trimmed = true;
+                return None;
+            }
+
+            for word in &mut line.words {
+                word.geometry = word.geometry.as_ref().map(to_image);
+            }
+            line.geometry = line.geometry.as_ref().map(to_image);
+            if line.words.len() < word_count {
+                trimmed = true;
+                line.geometry = enclose_words(line.geometry.as_ref(), &[&line], width, height);
+            }
+            line.text = line_text(&line);
+            Some(line)
+        })
+        .collect();
+
+    if paragraph.lines.is_empty() {
+        return None;
+    }
+
+    paragraph.geometry = paragraph.geometry.as_ref().map(to_image);
+    if trimmed {
+        let lines: Vec<&Line> = paragraph.lines.iter().collect();
+        paragraph.geometry = enclose_words(paragraph.geometry.as_ref(), &lines, width, height);
+    }
+    paragraph.text = paragraph
+        .lines
+        .iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(paragraph)
+}
+
+/// The smallest box at `geometry`'s angle that contains every word of `lines`, or `geometry`
+/// unchanged when no word has a box.
+fn enclose_words(
+    geometry: Option<&GeometryData>,
+    lines: &[&Line],
+    width: u32,
+    height: u32,
+) -> Option<GeometryData> {
+    let (rotation_z, angle_deg) = geometry.map_or((0.0, 0.0), |g| (g.rotation_z, g.angle_deg));
+    let (sin, cos) = rotation_z.sin_cos();
+
+    // Extent of every word corner along the box's own axes, in pixels.
+    let (mut min_u, mut min_v) = (f32::MAX, f32::MAX);
+    let (mut max_u, mut max_v) = (f32::MIN, f32::MIN);
+    let corners = lines
+        .iter()
+        .flat_map(|l| &l.words)
+        .filter_map(|w| w.geometry.as_ref())
+        .flat_map(|g| g.corners(width, height));
+    for p in corners {
+        let (u, v) = (p.x * cos + p.y * sin, -p.x * sin + p.y * cos);
+        min_u = min_u.min(u);
+        max_u = max_u.max(u);
+        min_v = min_v.min(v);
+        max_v = max_v.max(v);
+    }
+    if min_u > max_u {
+        return geometry.cloned();
+    }
+
+    let (u, v) = ((min_u + max_u) / 2.0, (min_v + max_v) / 2.0);
+    Some(GeometryData {
+        center_x: (u * cos - v * sin) / width as f32,
+        center_y: (u * sin + v * cos) / height as f32,
+        width: (max_u - min_u) / width as f32,
+        height: (max_v - min_v) / height as f32,
+        rotation_z,
+        angle_deg,
+    })
+}
+fn line_text(line: &Line) -> String {
+    let mut text = String::new();
+    for word in &line.words {
+        text.push_str(&word.text);
+        text.push_str(&word.separator);
+    }
+    text.trim().to_string()
+}
+
+fn tile_to_image(g: &GeometryData, tile: &Tile, width: u32, height: u32) -> GeometryData {
+    let sub = Rect::new(
+        tile.x as f32,
+        tile.y as f32,
+        tile.width as f32,
+        tile.height as f32,
+    );
+    g.map_from_subimage(sub, width, height)
+}
+
+fn is_owned(g: &GeometryData, tile: &Tile, width: u32, height: u32) -> bool {
+    let x = g.center_x * width as f32;
+    let y = g.center_y * height as f32;
+    let (left, top, right, bottom) = tile.owned;
+
+    x >= left as f32 && x < right as f32 && y >= top as f32 && y < bottom as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{geometry, line, paragraph, result, word};
+
+    /// Two tiles side by side on a 2000x500 image, sharing x 800..1200 with the seam at 1000.
+    fn two_tiles() -> Vec<Tile> {
+        let options = TilingOptions {
+            tile_size: 1200,
+            overlap: 400,
+            ..TilingOptions::default()
+        };
+        options.tiles(2000, 500).unwrap()
+    }
+
+    /// A box centered at image x `x`, `w` pixels wide, normalized to `tile`.
+    fn at(tile: &Tile, x: f32, w: f32) -> GeometryData {
+        geometry(
+            (x - tile.x as f32) / tile.width as f32,
+            0.5,
+            w / tile.width as f32,
+            0.1,
+            0.0,
+        )
+    }
+
+    fn single_word(tile: &Tile, text: &str, x: f32) -> Paragraph {
+        let g = at(tile, x, 100.0);
+        paragraph(
+            vec![line(vec![word(text, "", Some(g.clone()))], Some(g.clone()))],
+            Some(g),
+            None,
+        )
+    }
+
+    #[test]
+    fn owned_areas_partition_the_image() {
+        let cases = [
+            (1000, 700, 300, 50),
+            (3000, 3000, 1500, 200),
+            (1501, 10, 1500, 200),
+            (500, 500, 1500, 200),
+            (1000, 1000, 100, 60),
+        ];
+        for (width, height, tile_size, overlap) in cases {
+            let options = TilingOptions {
+                tile_size,
+                overlap,
+                ..TilingOptions::default()
+            };
+            let tiles = options.tiles(width, height).unwrap();
+
+            let mut area = 0;
+            for (i, tile) in tiles.iter().enumerate() {
+                let (left, top, right, bottom) = tile.owned;
+                assert!(left < right && top < bottom, "{:?}", tile);
+                assert!(left >= tile.x && right <= tile.x + tile.width, "{:?}", tile);
+                assert!(
+                    top >= tile.y && bottom <= tile.y + tile.height,
+                    "{:?}",
+                    tile
+                );
+                assert!(right <= width && bottom <= height, "{:?}", tile);
+                area += (right - left) as u64 * (bottom - top) as u64;
+
+                for other in &tiles[i + 1..] {
+                    let (l, t, r, b) = other.owned;
+                    let disjoint = r <= left || l >= right || b <= top || t >= bottom;
+                    assert!(disjoint, "{:?} overlaps {:?}", tile, other);
+                }
+            }
+            assert_eq!(area, width as u64 * height as u64);
+        }
+    }
+
+    #[test]
+    fn options_without_progress_are_rejected() {
+        for (tile_size, overlap) in [(0, 0), (1500, 1500), (1500, 2000)] {
+            let options = TilingOptions {
+                tile_size,
+                overlap,
+                ..TilingOptions::default()
+            };
+            assert!(
+                matches!(options.tiles(1000, 60_000), Err(LensError::Config(_))),
+                "{:?}",
+                options
+            );
+        }
+    }
+
+    #[test]
+    fn axis_offsets_stay_inside_the_image() {
+        assert_eq!(axis_offsets(900, 1000, 100), [(0, 900)]);
+        assert_eq!(
+            axis_offsets(2500, 1000, 200),
+            [(0, 1000), (800, 1000), (1500, 1000)]
+        );
+    }
+
+    #[test]
+    fn each_position_is_owned_by_one_tile() {
+        let tiles = two_tiles();
+        for x in (0..2000).step_by(7) {
+            let g = geometry(x as f32 / 2000.0, 0.5, 0.01, 0.01, 0.0);
+            let owners = tiles.iter().filter(|t| is_owned(&g, t, 2000, 500)).count();
+            assert_eq!(owners, 1, "x = {}", x);
+        }
+    }
+
+    #[test]
+    fn trimmed_lines_and_paragraphs_shrink_to_their_words() {
+        let tiles = two_tiles();
+        let tile = &tiles[0];
+        // "beta" lies past the seam, so tile 0 drops it.
+        let whole = at(tile, 675.0, 850.0);
+        let split = paragraph(
+            vec![line(
+                vec![
+                    word("alpha", " ", Some(at(tile, 300.0, 100.0))),
+                    word("beta", "", Some(at(tile, 1050.0, 100.0))),
+                ],
+                Some(whole.clone()),
+            )],
+            Some(whole),
+            None,
+        );
+
+        let stitched = stitch_paragraph(split, tile, 2000, 500).unwrap();
+        assert_eq!(stitched.text, "alpha");
+        for g in [&stitched.geometry, &stitched.lines[0].geometry] {
+            let rect = g.as_ref().unwrap().bounding_rect(2000, 500);
+            assert!((rect.x - 250.0).abs() < 0.5, "{:?}", rect);
+            assert!((rect.width - 100.0).abs() < 0.5, "{:?}", rect);
+        }
+    }
+
+    #[test]
+    fn merge_keeps_overlap_paragraphs_and_translations_once() {
+        let tiles = two_tiles();
+        let tile_result = |tile: &Tile, words: [(&str, f32); 2], translation: &str| {
+            let paragraphs = words
+                .iter()
+                .map(|&(text, x)| single_word(tile, text, x))
+                .collect();
+            let mut result = result(paragraphs, tile.width, tile.height);
+            result.translation = Some(translation.to_string());
+            Ok(result)
+        };
+        let results = vec![
+            tile_result(
+                &tiles[0],
+                [("alpha", 300.0), ("beta", 1050.0)],
+                "ALPHA\nBETA",
+            ),
+            tile_result(
+                &tiles[1],
+                [("beta", 1050.0), ("gamma", 1500.0)],
+                "BETA\nGAMMA",
+            ),
+        ];
+
+        let merged = merge_tiles(&tiles, results, 2000, 500).unwrap();
+        assert_eq!(merged.full_text, "alpha\nbeta\ngamma");
+        assert_eq!(merged.translation.as_deref(), Some("ALPHA\nBETA\nGAMMA"));
+    }
+}